@@ -0,0 +1,263 @@
+//! sed like addresses selecting the lines a program edits.
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Selects single lines.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// The line with this number, counted over all inputs.
+    Line(usize),
+    /// Lines matching the regular expression.
+    Regex(Regex),
+    /// Line `first` and every `step` lines after it, `first~step`.
+    Step { first: usize, step: usize },
+}
+
+impl PartialEq for Selector {
+    fn eq(&self, other: &Selector) -> bool {
+        match (self, other) {
+            (Selector::Line(a), Selector::Line(b)) => a == b,
+            (Selector::Regex(a), Selector::Regex(b)) => a.as_str() == b.as_str(),
+            (Selector::Step { first, step }, Selector::Step { first: f, step: s }) => {
+                first == f && step == s
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Selector {
+    fn matches(&self, number: usize, line: &str) -> bool {
+        match self {
+            Selector::Line(selected) => number == *selected,
+            Selector::Regex(regex) => regex.is_match(line),
+            Selector::Step { first, step: 0 } => number == *first,
+            Selector::Step { first, step } => {
+                number >= *first && (number - first).is_multiple_of(*step)
+            }
+        }
+    }
+}
+
+/// The lines a program applies to. Other lines pass through unedited.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    Single(Selector),
+    /// The lines from one selected by the first selector up to and including
+    /// the next one selected by the second, as in sed.
+    Range(Selector, Selector),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AddressError {
+    pub address: String,
+    pub reason: String,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid address `{}`: {}.", self.address, self.reason)
+    }
+}
+
+impl Error for AddressError {}
+
+/// Parses a selector at the start of `text`, returning it and the rest of the text.
+fn selector(text: &str) -> Result<(Selector, &str), String> {
+    if let Some(rest) = text.strip_prefix('/') {
+        let mut pattern = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '/' => {
+                    let regex = Regex::new(&pattern).map_err(|err| err.to_string())?;
+                    return Ok((Selector::Regex(regex), &rest[pos + 1..]));
+                }
+                '\\' => match chars.next() {
+                    Some((_, '/')) => pattern.push('/'),
+                    Some((_, c)) => {
+                        pattern.push('\\');
+                        pattern.push(c);
+                    }
+                    None => break,
+                },
+                c => pattern.push(c),
+            }
+        }
+        return Err("unterminated regular expression".to_owned());
+    }
+
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number = text[..digits]
+        .parse()
+        .map_err(|_| "expected a line number or /regex/".to_owned())?;
+    let rest = &text[digits..];
+    if let Some(rest) = rest.strip_prefix('~') {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let step = rest[..digits]
+            .parse()
+            .map_err(|_| "expected a step after `~`".to_owned())?;
+        return Ok((
+            Selector::Step {
+                first: number,
+                step,
+            },
+            &rest[digits..],
+        ));
+    }
+    Ok((Selector::Line(number), rest))
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    /// Parses `N`, `N,M`, `/regex/`, `/start/,/end/` or `first~step`.
+    fn from_str(text: &str) -> Result<Address, AddressError> {
+        let error = |reason: String| AddressError {
+            address: text.to_owned(),
+            reason,
+        };
+
+        let (first, rest) = selector(text.trim()).map_err(error)?;
+        let address = match rest.strip_prefix(',') {
+            Some(rest) => {
+                let (last, rest) = selector(rest).map_err(error)?;
+                if !rest.is_empty() {
+                    return Err(error(format!("unexpected `{}`", rest)));
+                }
+                Address::Range(first, last)
+            }
+            None if rest.is_empty() => Address::Single(first),
+            None => return Err(error(format!("unexpected `{}`", rest))),
+        };
+        Ok(address)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Line(line) => write!(f, "{}", line),
+            Selector::Regex(regex) => write!(f, "/{}/", regex.as_str().replace('/', "\\/")),
+            Selector::Step { first, step } => write!(f, "{}~{}", first, step),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Single(selector) => write!(f, "{}", selector),
+            Address::Range(first, last) => write!(f, "{},{}", first, last),
+        }
+    }
+}
+
+/// Addresses are serialized as the text they are parsed from.
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Tracks which lines an address selects, line by line.
+pub struct Selection<'a> {
+    address: Option<&'a Address>,
+    in_range: bool,
+}
+
+impl<'a> Selection<'a> {
+    /// Selects the lines of `address`, or every line if there is none.
+    pub fn new(address: Option<&'a Address>) -> Selection<'a> {
+        Selection {
+            address,
+            in_range: false,
+        }
+    }
+
+    /// Whether the line with `number` is selected. Lines must be passed in order.
+    pub fn selects(&mut self, number: usize, line: &str) -> bool {
+        match self.address {
+            None => true,
+            Some(Address::Single(selector)) => selector.matches(number, line),
+            Some(Address::Range(first, last)) => {
+                if self.in_range {
+                    self.in_range = match last {
+                        Selector::Line(last) => number < *last,
+                        last => !last.matches(number, line),
+                    };
+                    true
+                } else if first.matches(number, line) {
+                    // A line number at or before the start ends the range at once.
+                    self.in_range = match last {
+                        Selector::Line(last) => number < *last,
+                        _ => true,
+                    };
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(address: &str, lines: &[&str]) -> Vec<usize> {
+        let address: Address = address.parse().unwrap();
+        let mut selection = Selection::new(Some(&address));
+        (1..=lines.len())
+            .filter(|&number| selection.selects(number, lines[number - 1]))
+            .collect()
+    }
+
+    #[test]
+    fn select_lines() {
+        let lines = ["a", "start", "b", "end", "c", "start", "d"];
+        assert_eq!(vec![3], selected("3", &lines));
+        assert_eq!(vec![2, 3, 4], selected("2,4", &lines));
+        assert_eq!(vec![5], selected("5,2", &lines));
+        assert_eq!(vec![2, 6], selected("/st.rt/", &lines));
+        assert_eq!(vec![2, 3, 4, 6, 7], selected("/start/,/end/", &lines));
+        assert_eq!(vec![1, 2, 3], selected("1,/b/", &lines));
+        assert_eq!(vec![4, 5], selected("/end/,5", &lines));
+        assert_eq!(vec![2, 5], selected("2~3", &lines));
+        assert_eq!(vec![3, 6], selected("0~3", &lines));
+        assert_eq!(vec![1, 2, 3], selected(r"/\//,3", &["a/b", "x", "y", "z"]));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for text in &["3", "2,4", "/st.rt/", r"/\//,/a\.b/", "0~3"] {
+            let address: Address = text.parse().unwrap();
+            assert_eq!(*text, address.to_string());
+            assert_eq!(address, address.to_string().parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        let reason = |text: &str| text.parse::<Address>().unwrap_err().reason;
+        assert_eq!("unterminated regular expression", reason("/abc"));
+        assert_eq!("expected a line number or /regex/", reason("x"));
+        assert_eq!("unexpected `;`", reason("1,2;"));
+        assert_eq!("expected a step after `~`", reason("1~"));
+    }
+}
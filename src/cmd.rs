@@ -1,25 +1,78 @@
 use super::cmds::character::*;
 use super::cmds::clipboard::*;
 use super::cmds::control::*;
+use super::cmds::hold::*;
 use super::cmds::insert::*;
 use super::cmds::kill::*;
 use super::cmds::line::*;
 use super::cmds::navigation::*;
+use super::cmds::output::*;
 use super::cmds::region::*;
+use super::cmds::register::*;
 use super::cmds::search::*;
 use super::cmds::word::*;
 use std::cmp;
 use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::mem;
 use std::ops::Range;
 use std::ops::RangeInclusive;
 
+/// Where the line being edited comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    /// The name of the input file, `-` for stdin.
+    pub file: String,
+    /// The line number counted over all inputs, from 1.
+    pub line: usize,
+    /// The line number within the current file, from 1.
+    pub file_line: usize,
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position {
+            file: "-".to_owned(),
+            line: 1,
+            file_line: 1,
+        }
+    }
+}
+
+/// The state a program can carry from line to line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Store {
+    pub clipboard: Vec<Vec<char>>,
+    pub registers: HashMap<String, Vec<char>>,
+    pub counters: HashMap<String, i64>,
+    /// The hold space, which lives for the whole input even when nothing
+    /// else persists.
+    pub hold: Vec<char>,
+}
+
 #[derive(Debug)]
 pub struct LineState {
     pub cursor: usize,
     pub characters: Vec<char>,
     pub mark: Option<usize>,
     pub clipboard: Vec<Vec<char>>,
+    pub registers: HashMap<String, Vec<char>>,
+    pub counters: HashMap<String, i64>,
+    /// The hold space, empty until something is held.
+    pub hold: Vec<char>,
     pub done_with_line: bool,
+    pub position: Position,
+    /// Lines written before the edited line.
+    pub emitted: Vec<String>,
+    /// Lines written after the edited line.
+    pub appended: Vec<String>,
+    /// The edited line is left out of the output.
+    pub deleted: bool,
+    /// The separator of a `join_next` waiting for the next input line.
+    pub joining: Option<String>,
+    /// Case changes only affect ASCII letters, as in records edited byte by byte.
+    pub ascii_case: bool,
 }
 
 impl LineState {
@@ -31,7 +84,41 @@ impl LineState {
             characters,
             mark: None,
             clipboard: Vec::new(),
+            registers: HashMap::new(),
+            counters: HashMap::new(),
+            hold: Vec::new(),
             done_with_line: false,
+            position: Position::default(),
+            emitted: Vec::new(),
+            appended: Vec::new(),
+            deleted: false,
+            joining: None,
+            ascii_case: false,
+        }
+    }
+
+    pub fn at_position(line: &str, position: Position) -> LineState {
+        LineState {
+            position,
+            ..LineState::new(line)
+        }
+    }
+
+    /// Continues from the clipboard, registers, counters and hold space in `store`.
+    pub fn load_store(&mut self, store: Store) {
+        self.clipboard = store.clipboard;
+        self.registers = store.registers;
+        self.counters = store.counters;
+        self.hold = store.hold;
+    }
+
+    /// Moves the clipboard, registers, counters and hold space out of the state.
+    pub fn take_store(&mut self) -> Store {
+        Store {
+            clipboard: mem::take(&mut self.clipboard),
+            registers: mem::take(&mut self.registers),
+            counters: mem::take(&mut self.counters),
+            hold: mem::take(&mut self.hold),
         }
     }
 
@@ -42,6 +129,10 @@ impl LineState {
         self.clipboard.clear();
         self.characters = characters;
         self.done_with_line = false;
+        self.emitted.clear();
+        self.appended.clear();
+        self.deleted = false;
+        self.joining = None;
     }
     // pub fn after_cursor(&self) -> RangeInclusive<usize> {
     //     self.cursor + 1..=self.cursor + 1
@@ -68,7 +159,25 @@ impl LineState {
     }
 
     pub fn last_character_position(&self) -> usize {
-        self.characters.len() - 1
+        self.characters.len().saturating_sub(1)
+    }
+
+    /// `chars` in upper case.
+    pub fn upcase_chars(&self, chars: &[char]) -> Vec<char> {
+        if self.ascii_case {
+            chars.iter().map(char::to_ascii_uppercase).collect()
+        } else {
+            chars.iter().flat_map(|c| c.to_uppercase()).collect()
+        }
+    }
+
+    /// `chars` in lower case.
+    pub fn downcase_chars(&self, chars: &[char]) -> Vec<char> {
+        if self.ascii_case {
+            chars.iter().map(char::to_ascii_lowercase).collect()
+        } else {
+            chars.iter().flat_map(|c| c.to_lowercase()).collect()
+        }
     }
 
     pub fn at_character(&self) -> bool {
@@ -83,6 +192,35 @@ impl LineState {
         self.cursor..self.cursor
     }
 
+    /// Renders the line the way the tests draw it, with the cursor as `|`
+    /// and the mark as `^`.
+    pub fn render(&self) -> String {
+        let mut rendered = String::new();
+        for pos in 0..=self.characters.len() {
+            if pos == self.cursor {
+                rendered.push('|');
+            }
+            if self.mark == Some(pos) {
+                rendered.push('^');
+            }
+            if let Some(&c) = self.characters.get(pos) {
+                rendered.push(c);
+            }
+        }
+        rendered
+    }
+
+    /// The lines the edited line turns into: the emitted lines, the line
+    /// itself unless deleted, and the appended lines.
+    pub fn output_lines(&self) -> Vec<String> {
+        let mut lines = self.emitted.clone();
+        if !self.deleted {
+            lines.push(self.characters.iter().collect());
+        }
+        lines.extend(self.appended.iter().cloned());
+        lines
+    }
+
     #[cfg(test)]
     pub fn text(&self) -> String {
         self.characters.iter().collect()
@@ -94,7 +232,30 @@ impl LineState {
     }
 }
 
-#[derive(Debug)]
+/// A test on where the edited line comes from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The line number over all inputs is this.
+    Line(usize),
+    /// The line number within the current file is this.
+    FileLine(usize),
+    /// The current file has this name, as given on the command line.
+    File(String),
+}
+
+impl Condition {
+    pub fn holds(&self, position: &Position) -> bool {
+        match self {
+            Condition::Line(line) => position.line == *line,
+            Condition::FileLine(line) => position.file_line == *line,
+            Condition::File(file) => position.file == *file,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Cmd {
     // Navigation
     Back,
@@ -105,6 +266,12 @@ pub enum Cmd {
     End,
     Last,
     Goto(usize),
+    ForwardWordStart,
+    BackWordStart,
+    ForwardLine,
+    BackLine,
+    LineStart,
+    LineEnd,
 
     // Character commands
     Delete,
@@ -113,6 +280,7 @@ pub enum Cmd {
     UpcaseCharacter,
     DowncaseCharacter,
     Translate { table: HashMap<char, char> },
+    Substitute { from: String, to: String, all: bool },
     CopyLine,
 
     // Word commands
@@ -126,6 +294,8 @@ pub enum Cmd {
 
     // Region commands
     Mark,
+    ExchangeMark,
+    SelectWord,
     UpcaseRegion,
     DowncaseRegion,
     Transpose,
@@ -133,6 +303,7 @@ pub enum Cmd {
     // Clipboard
     Copy,
     Paste,
+    PasteAfter,
     Cut,
     UpcaseClipboard,
     DowncaseClipboard,
@@ -154,12 +325,37 @@ pub enum Cmd {
 
     // Searching
     Find { what: char },
+    FindNext { what: char },
     RFind { what: char },
 
+    // Output
+    DeleteLine,
+    Print,
+    PrintClipboard,
+    Emit { what: String },
+    EmitAfter { what: String },
+    SplitLine,
+    JoinNext { separator: String },
+
+    // Registers and counters
+    StoreRegister { name: String },
+    InsertRegister { name: String },
+    Increment { name: String },
+    InsertCounter { name: String },
+
+    // Hold space
+    Hold,
+    HoldAppend,
+    Get,
+    GetAppend,
+    Exchange,
+
     // Other
     NextLine,
     Repeat { times: usize, cmd: Box<Cmd> },
     Insert { what: String },
+    InsertTemplate { template: String },
+    If { condition: Condition, cmd: Box<Cmd> },
     Block(Vec<Cmd>),
 }
 
@@ -174,10 +370,18 @@ impl Cmd {
             Cmd::Home => home(state),
             Cmd::End => end(state),
             Cmd::Goto(column) => goto(state, *column),
+            Cmd::ForwardWordStart => forward_word_start(state),
+            Cmd::BackWordStart => back_word_start(state),
+            Cmd::ForwardLine => next_line(state),
+            Cmd::BackLine => prev_line(state),
+            Cmd::LineStart => line_start(state),
+            Cmd::LineEnd => line_end(state),
             Cmd::Last => last(state),
             Cmd::Delete => delete(state),
             Cmd::DeleteBefore => delete_before(state),
             Cmd::Mark => mark(state),
+            Cmd::ExchangeMark => exchange_mark(state),
+            Cmd::SelectWord => select_word(state),
             Cmd::Copy => copy(state),
             Cmd::CopyLine => copy_line(state),
             Cmd::TruncateBy(amount) => truncate_by(state, *amount),
@@ -196,22 +400,50 @@ impl Cmd {
             Cmd::RTrimLine => rtrim_line(state),
             Cmd::Cut => cut(state),
             Cmd::Paste => paste(state),
+            Cmd::PasteAfter => paste_after(state),
             Cmd::Repeat { times, cmd } => repeat(state, *times, cmd),
-            Cmd::Insert { what } => insert(&what, state),
+            Cmd::Insert { what } => insert(what, state),
+            Cmd::InsertTemplate { template } => {
+                let text = expand_template(template, &state.position);
+                insert(&text, state)
+            }
+            Cmd::If { condition, cmd } => {
+                if condition.holds(&state.position) {
+                    cmd.eval(state)
+                }
+            }
             Cmd::Find { what } => state.done_with_line = !find(*what, state),
+            Cmd::FindNext { what } => state.done_with_line = !find_next(*what, state),
             Cmd::RFind { what } => state.done_with_line = !rfind(*what, state),
             Cmd::Kill => kill(state),
             Cmd::Transpose => transpose(state),
             Cmd::NextLine => state.done_with_line = true,
+            Cmd::DeleteLine => delete_line(state),
+            Cmd::Print => print(state),
+            Cmd::PrintClipboard => print_clipboard(state),
+            Cmd::Emit { what } => emit(what, state),
+            Cmd::EmitAfter { what } => emit_after(what, state),
+            Cmd::SplitLine => split_line(state),
+            Cmd::JoinNext { separator } => join_next(separator, state),
+            Cmd::StoreRegister { name } => store_register(name, state),
+            Cmd::InsertRegister { name } => insert_register(name, state),
+            Cmd::Increment { name } => increment(name, state),
+            Cmd::InsertCounter { name } => insert_counter(name, state),
+            Cmd::Hold => hold(state),
+            Cmd::HoldAppend => hold_append(state),
+            Cmd::Get => get(state),
+            Cmd::GetAppend => get_append(state),
+            Cmd::Exchange => exchange(state),
             Cmd::Translate { table } => translate(state, table),
+            Cmd::Substitute { from, to, all } => substitute(state, from, to, *all),
             Cmd::Block(ref cmds) => block(cmds, state),
             Cmd::TransposeCharacter => transpose_character(state),
             Cmd::UpcaseCharacter => upcase_character(state),
             Cmd::DowncaseCharacter => downcase_character(state),
             Cmd::KillWord => kill_word(state),
-            Cmd::KillFullWord => reverse_kill_word(state),
-            Cmd::RKillWord => kill_full_word(state),
-            Cmd::TransposeWord => kill_full_word(state),
+            Cmd::KillFullWord => kill_full_word(state),
+            Cmd::RKillWord => reverse_kill_word(state),
+            Cmd::TransposeWord => transpose_word(state),
             Cmd::UpcaseWord => upcase_word(state),
             Cmd::DowncaseWord => downcase_word(state),
             Cmd::SentenceCaseWord => sentence_case_word(state),
@@ -221,6 +453,143 @@ impl Cmd {
     }
 }
 
+struct Frame<'a> {
+    cmds: &'a [Cmd],
+    pc: usize,
+    passes: usize,
+}
+
+/// A program being executed one command at a time, entering `Repeat` and
+/// `Block` commands instead of evaluating them as a whole.
+pub struct Execution<'a> {
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Execution<'a> {
+    pub fn new(program: &'a [Cmd]) -> Execution<'a> {
+        let mut execution = Execution {
+            frames: vec![Frame {
+                cmds: program,
+                pc: 0,
+                passes: 1,
+            }],
+        };
+        execution.settle();
+        execution
+    }
+
+    /// Pops finished frames so that the top frame points at the next command to execute.
+    fn settle(&mut self) {
+        while let Some(frame) = self.frames.last_mut() {
+            if frame.pc < frame.cmds.len() {
+                return;
+            }
+            if frame.passes > 1 && !frame.cmds.is_empty() {
+                frame.passes -= 1;
+                frame.pc = 0;
+                return;
+            }
+            self.frames.pop();
+            if let Some(parent) = self.frames.last_mut() {
+                parent.pc += 1;
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Nesting depth of the next command, 1 for top level commands.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Index of the top level command being executed.
+    pub fn position(&self) -> Option<usize> {
+        self.frames.first().map(|frame| frame.pc)
+    }
+
+    /// The next command to execute.
+    pub fn current(&self) -> Option<&'a Cmd> {
+        self.frames.last().map(|frame| &frame.cmds[frame.pc])
+    }
+
+    /// Executes a single command, entering repeat and block commands. Returns
+    /// the executed command.
+    pub fn step(&mut self, state: &mut LineState) -> Option<&'a Cmd> {
+        while let Some(cmd) = self.current() {
+            let frame = match cmd {
+                Cmd::Repeat { times, cmd } => Frame {
+                    cmds: ::std::slice::from_ref(&**cmd),
+                    pc: 0,
+                    passes: *times,
+                },
+                Cmd::Block(cmds) => Frame {
+                    cmds,
+                    pc: 0,
+                    passes: 1,
+                },
+                Cmd::If { condition, cmd } => Frame {
+                    cmds: ::std::slice::from_ref(&**cmd),
+                    pc: 0,
+                    passes: if condition.holds(&state.position) { 1 } else { 0 },
+                },
+                _ => return self.next(state),
+            };
+            if frame.passes == 0 || frame.cmds.is_empty() {
+                self.frames.last_mut().unwrap().pc += 1;
+            } else {
+                self.frames.push(frame);
+            }
+            self.settle();
+        }
+        None
+    }
+
+    /// Executes the next command in full, without entering it. Returns the
+    /// executed command. Execution ends once the state is done with the line.
+    pub fn next(&mut self, state: &mut LineState) -> Option<&'a Cmd> {
+        let cmd = self.current()?;
+        cmd.eval(state);
+        if state.done_with_line {
+            self.frames.clear();
+            return Some(cmd);
+        }
+        self.frames.last_mut().unwrap().pc += 1;
+        self.settle();
+        Some(cmd)
+    }
+}
+
+/// Replaces `{file}`, `{line}` and `{file_line}` in `template` with the
+/// position of the line. `{{` and `}}` stand for `{` and `}`.
+pub fn expand_template(template: &str, position: &Position) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let (text, len) = if rest.starts_with("{{") {
+            ("{".to_owned(), 2)
+        } else if rest.starts_with("}}") {
+            ("}".to_owned(), 2)
+        } else if rest.starts_with("{file}") {
+            (position.file.clone(), 6)
+        } else if rest.starts_with("{line}") {
+            (position.line.to_string(), 6)
+        } else if rest.starts_with("{file_line}") {
+            (position.file_line.to_string(), 11)
+        } else {
+            (rest[..1].to_owned(), 1)
+        };
+        expanded.push_str(&text);
+        rest = &rest[len..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
 pub fn translate(state: &mut LineState, table: &HashMap<char, char>) {
     state.characters.iter_mut().for_each(|c| {
         if let Some(&t) = table.get(c) {
@@ -229,12 +598,258 @@ pub fn translate(state: &mut LineState, table: &HashMap<char, char>) {
     });
 }
 
-pub fn run(program: &[Cmd], line: &str) -> String {
-    let mut state = LineState::new(line);
+/// Runs `program` on `line`, returning the lines it turns into.
+pub fn run(program: &[Cmd], line: &str) -> Vec<String> {
+    run_at(program, line, Position::default())
+}
+
+/// Same as `run` for a line at `position`.
+pub fn run_at(program: &[Cmd], line: &str, position: Position) -> Vec<String> {
+    run_on_input(program, line, position, &mut || None, &mut Store::default(), false)
+}
+
+/// Executes the next command, appending the next line of `input` if it asks
+/// for one.
+fn step<'a>(
+    execution: &mut Execution<'a>,
+    state: &mut LineState,
+    input: &mut dyn FnMut() -> Option<String>,
+) -> Option<&'a Cmd> {
+    let cmd = execution.step(state)?;
+    join_input(state, input);
+    Some(cmd)
+}
 
-    for cmd in program {
-        cmd.eval(&mut state);
+/// Appends the next line of `input` if the last command asked for one.
+pub fn join_input(state: &mut LineState, input: &mut dyn FnMut() -> Option<String>) {
+    if let Some(separator) = state.joining.take() {
+        if let Some(next) = input() {
+            join(&separator, &next, state);
+        }
     }
+}
+
+/// Same as `run_at`, taking the lines `join_next` appends from `input` and
+/// starting from and updating the clipboard, registers and counters in `store`.
+/// With `ascii_case`, case changes only affect ASCII letters.
+pub fn run_on_input(
+    program: &[Cmd],
+    line: &str,
+    position: Position,
+    input: &mut dyn FnMut() -> Option<String>,
+    store: &mut Store,
+    ascii_case: bool,
+) -> Vec<String> {
+    let mut state = LineState::at_position(line, position);
+    state.ascii_case = ascii_case;
+    state.load_store(mem::take(store));
+    let mut execution = Execution::new(program);
+    while step(&mut execution, &mut state, input).is_some() {}
+    *store = state.take_store();
+    state.output_lines()
+}
 
-    state.characters.iter().collect()
+/// Runs an `at_begin` or `at_end` block on an empty line, which is left out
+/// of the output unless the block puts text in it.
+pub fn run_block(
+    program: &[Cmd],
+    position: Position,
+    store: &mut Store,
+    ascii_case: bool,
+) -> Vec<String> {
+    let mut state = LineState::at_position("", position);
+    state.ascii_case = ascii_case;
+    state.load_store(mem::take(store));
+    let mut execution = Execution::new(program);
+    while step(&mut execution, &mut state, &mut || None).is_some() {}
+    state.deleted |= state.characters.is_empty();
+    *store = state.take_store();
+    state.output_lines()
+}
+
+/// Same as `run_on_input` but writes every executed command and the
+/// resulting line state to `trace`.
+pub fn run_traced(
+    program: &[Cmd],
+    line: &str,
+    position: Position,
+    input: &mut dyn FnMut() -> Option<String>,
+    store: &mut Store,
+    ascii_case: bool,
+    trace: &mut dyn Write,
+) -> io::Result<Vec<String>> {
+    let mut state = LineState::at_position(line, position);
+    state.ascii_case = ascii_case;
+    state.load_store(mem::take(store));
+    writeln!(trace, "{:<24} {}", "", state.render())?;
+
+    let mut execution = Execution::new(program);
+    while let Some(cmd) = step(&mut execution, &mut state, input) {
+        writeln!(trace, "{:<24} {}", format!("{:?}", cmd), state.render())?;
+    }
+
+    *store = state.take_store();
+    Ok(state.output_lines())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_line_state() {
+        let mut state = LineState::new("navigation");
+        assert_eq!("|navigation", state.render());
+
+        state.mark = Some(0);
+        assert_eq!("|^navigation", state.render());
+
+        state.cursor = 3;
+        assert_eq!("^nav|igation", state.render());
+
+        state.cursor = 10;
+        state.mark = Some(10);
+        assert_eq!("navigation|^", state.render());
+    }
+
+    #[test]
+    fn eval_kill_word_cmds() {
+        let mut state = LineState::new("one two three");
+        state.cursor = 5;
+        Cmd::KillFullWord.eval(&mut state);
+        assert_eq!("one | three", state.render());
+
+        let mut state = LineState::new("one two three");
+        state.cursor = 5;
+        Cmd::RKillWord.eval(&mut state);
+        assert_eq!("one |wo three", state.render());
+    }
+
+    #[test]
+    fn execution_steps_into_repeat_and_block() {
+        let program = vec![
+            Cmd::Repeat {
+                times: 2,
+                cmd: Box::new(Cmd::Forward),
+            },
+            Cmd::Block(vec![Cmd::Mark, Cmd::End]),
+            Cmd::Repeat {
+                times: 0,
+                cmd: Box::new(Cmd::Home),
+            },
+        ];
+        let mut state = LineState::new("navigation");
+        let mut execution = Execution::new(&program);
+
+        assert_eq!(Some(&Cmd::Forward), execution.step(&mut state));
+        assert_eq!(2, execution.depth());
+        assert_eq!(Some(&Cmd::Forward), execution.step(&mut state));
+        assert_eq!("na|vigation", state.render());
+
+        assert_eq!(Some(1), execution.position());
+        assert_eq!(1, execution.depth());
+        assert_eq!(Some(&Cmd::Mark), execution.step(&mut state));
+        assert_eq!(Some(&Cmd::End), execution.next(&mut state));
+        assert_eq!("na^vigation|", state.render());
+
+        // Repeating zero times executes nothing.
+        assert_eq!(None, execution.step(&mut state));
+        assert!(execution.is_done());
+    }
+
+    #[test]
+    fn run_traced_cmds() {
+        let mut trace = Vec::new();
+        let program = vec![Cmd::Mark, Cmd::ForwardWord, Cmd::UpcaseRegion];
+        let line = run_traced(
+            &program,
+            "Back,",
+            Position::default(),
+            &mut || None,
+            &mut Store::default(),
+            false,
+            &mut trace,
+        );
+
+        assert_eq!(vec!["BACK,"], line.unwrap());
+        let trace = String::from_utf8(trace).unwrap();
+        let states: Vec<&str> = trace.lines().map(|l| l[25..].trim()).collect();
+        assert_eq!(vec!["|Back,", "|^Back,", "^Back|,", "^BACK|,"], states);
+    }
+
+    #[test]
+    fn templates_and_conditions_use_position() {
+        let position = Position {
+            file: "src/cmd.rs".to_owned(),
+            line: 12,
+            file_line: 2,
+        };
+        assert_eq!(
+            "src/cmd.rs:2 (12) {x}",
+            expand_template("{file}:{file_line} ({line}) {{x}}", &position)
+        );
+
+        let program = vec![
+            Cmd::If {
+                condition: Condition::FileLine(2),
+                cmd: Box::new(Cmd::InsertTemplate {
+                    template: "{file}: ".to_owned(),
+                }),
+            },
+            Cmd::If {
+                condition: Condition::File("other.rs".to_owned()),
+                cmd: Box::new(Cmd::UpcaseLine),
+            },
+        ];
+        assert_eq!(vec!["src/cmd.rs: line"], run_at(&program, "line", position));
+        assert_eq!(vec!["line"], run(&program, "line"));
+    }
+
+    #[test]
+    fn join_lines_from_input() {
+        let program = vec![Cmd::Repeat {
+            times: 2,
+            cmd: Box::new(Cmd::JoinNext {
+                separator: ",".to_owned(),
+            }),
+        }];
+        let mut input = vec!["c".to_owned(), "b".to_owned()];
+        let mut next = || input.pop();
+        let mut store = Store::default();
+        let mut run =
+            |line| run_on_input(&program, line, Position::default(), &mut next, &mut store, false);
+
+        assert_eq!(vec!["a,b,c"], run("a"));
+        assert_eq!(vec!["d"], run("d"));
+    }
+
+    #[test]
+    fn store_carries_over_lines() {
+        let program = vec![
+            Cmd::Increment {
+                name: "n".to_owned(),
+            },
+            Cmd::InsertCounter {
+                name: "n".to_owned(),
+            },
+            Cmd::CopyLine,
+        ];
+        let mut store = Store::default();
+        let mut run = |line| {
+            let position = Position::default();
+            run_on_input(&program, line, position, &mut || None, &mut store, false)
+        };
+
+        assert_eq!(vec!["1a"], run("a"));
+        assert_eq!(vec!["2b"], run("b"));
+        assert_eq!(2, store.clipboard.len());
+        assert_eq!(Some(&2), store.counters.get("n"));
+
+        let end = vec![Cmd::Emit {
+            what: "end".to_owned(),
+        }];
+        assert_eq!(vec!["end"], run_block(&end, Position::default(), &mut store, false));
+        let counter = run_block(&program[..2], Position::default(), &mut store, false);
+        assert_eq!(vec!["3"], counter);
+    }
 }
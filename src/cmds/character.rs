@@ -27,7 +27,7 @@ pub fn transpose_character(state: &mut LineState) {
 /// Upcases the character at the cursor position, Does nothing if the cursor is in the END position.
 pub fn upcase_character(state: &mut LineState) {
     if state.at_character() {
-        let upcased = state.characters[state.cursor].to_uppercase();
+        let upcased = state.upcase_chars(&state.characters[state.at_cursor()]);
         let target = state.at_cursor();
         state.characters.splice(target, upcased);
     }
@@ -36,13 +36,14 @@ pub fn upcase_character(state: &mut LineState) {
 /// Downcases the character at the cursor position. Does nothing if the cursor is in the END position.  
 pub fn downcase_character(state: &mut LineState) {
     if state.at_character() {
-        let downcased = state.characters[state.cursor].to_lowercase();
+        let downcased = state.downcase_chars(&state.characters[state.at_cursor()]);
         let target = state.at_cursor();
         state.characters.splice(target, downcased);
     }
 }
 
 /// Translates the character at the cursor position. Does nothing if the cursor is in the END position.
+#[allow(dead_code)]
 pub fn translate_char(state: &mut LineState, table: &HashMap<char, char>) {
     if state.at_character() {
         if let Some(replacement) = table.get(&state.characters[state.cursor]) {
@@ -66,6 +67,7 @@ pub fn delete_before(state: &mut LineState) {
     }
 }
 
+#[allow(dead_code)]
 pub fn translate(state: &mut LineState, table: &HashMap<char, char>) {
     state.characters.iter_mut().for_each(|c| {
         if let Some(&t) = table.get(c) {
@@ -76,7 +78,6 @@ pub fn translate(state: &mut LineState, table: &HashMap<char, char>) {
 
 #[cfg(test)]
 mod tests {
-    use super::super::clipboard::*;
     use super::super::navigation::*;
     use super::*;
 
@@ -85,13 +86,6 @@ mod tests {
     const NAV_LAST: usize = 10;
     const NAV_LAST_CHARP: usize = 9;
 
-    fn copy_all(state: &mut LineState) {
-        home(state);
-        mark(state);
-        end(state);
-        copy(state);
-    }
-
     #[test]
     fn delete_cmd() {
         let mut state = LineState::new(SAMPLE);
@@ -1,6 +1,7 @@
 use super::super::LineState;
 
 use super::region;
+use std::cmp;
 
 pub fn mark(state: &mut LineState) {
     state.mark = Some(state.cursor);
@@ -10,7 +11,7 @@ pub fn copy(state: &mut LineState) {
     let region::Region { start, end } = region::region(state);
     state
         .clipboard
-        .push(state.characters[start..end].iter().cloned().collect());
+        .push(state.characters[start..end].to_vec());
 }
 
 pub fn cut(state: &mut LineState) {
@@ -32,38 +33,57 @@ pub fn paste(state: &mut LineState) {
     }
 }
 
+/// Pastes after the character at the cursor and leaves the cursor on the
+/// last pasted character, like vim's `p`.
+pub fn paste_after(state: &mut LineState) {
+    if let Some(text) = state.clipboard.last() {
+        if !text.is_empty() {
+            let at = cmp::min(state.cursor + 1, state.characters.len());
+            let contents = text.clone();
+            state.cursor = at + contents.len() - 1;
+            state.characters.splice(at..at, contents);
+        }
+    }
+}
+
+/// Swaps the cursor and the mark, if there is a mark.
+pub fn exchange_mark(state: &mut LineState) {
+    if let Some(mark) = state.mark {
+        state.mark = Some(state.cursor);
+        state.cursor = mark;
+    }
+}
+
 pub fn upcase_clipboard(state: &mut LineState) {
     if let Some(text) = state.clipboard.pop() {
-        state
-            .clipboard
-            .push(text.iter().flat_map(|c| c.to_uppercase()).collect());
+        let upcased = state.upcase_chars(&text);
+        state.clipboard.push(upcased);
     }
 }
 
 pub fn downcase_clipboard(state: &mut LineState) {
     if let Some(text) = state.clipboard.pop() {
-        state
-            .clipboard
-            .push(text.iter().flat_map(|c| c.to_lowercase()).collect());
+        let downcased = state.downcase_chars(&text);
+        state.clipboard.push(downcased);
     }
 }
 
 pub fn sentencecase_clipboard(state: &mut LineState) {
     if let Some(mut text) = state.clipboard.pop() {
         if !text.is_empty() {
-            let replacement = text.remove(0).to_uppercase();
-            text.splice(0..0, replacement);
+            let replacement = state.upcase_chars(&text[..1]);
+            text.splice(0..1, replacement);
         }
         state.clipboard.push(text);
     }
 }
 
-fn left_remove_until(v: &mut Vec<char>, pred: &Fn(&char) -> bool) {
+fn left_remove_until(v: &mut Vec<char>, pred: &dyn Fn(&char) -> bool) {
     let index = v.iter().position(pred).unwrap_or(v.len());
     v.drain(0..index);
 }
 
-fn right_remove_until(v: &mut Vec<char>, pred: &Fn(&char) -> bool) {
+fn right_remove_until(v: &mut Vec<char>, pred: &dyn Fn(&char) -> bool) {
     let index = v.len() - v.iter().rev().position(pred).unwrap_or(0);
     v.truncate(index);
 }
@@ -95,13 +115,7 @@ mod tests {
     use super::super::navigation::*;
     use super::*;
 
-    const SAMPLE_BACK: &str = "   Back, ";
-    const BACK_LEN: usize = 9;
-    const BACK_LAST: usize = 9;
     const SAMPLE_NAV: &str = "navigation";
-    const NAV_LEN: usize = 10;
-    const NAV_LAST: usize = 10;
-    const NAV_LAST_CHARP: usize = 9;
 
     #[test]
     fn mark_cmd() {
@@ -222,6 +236,21 @@ mod tests {
         assert_eq!(Some(0), state.mark);
     }
 
+    #[test]
+    fn paste_after_cmd() {
+        let mut state = LineState::new("ab");
+        state.clipboard.push(vec!['x', 'y']);
+
+        paste_after(&mut state);
+        assert_eq!("axyb", state.text());
+        assert_eq!(2, state.cursor);
+
+        end(&mut state);
+        paste_after(&mut state);
+        assert_eq!("axybxy", state.text());
+        assert_eq!(5, state.cursor);
+    }
+
     fn copy_all(state: &mut LineState) {
         home(state);
         mark(state);
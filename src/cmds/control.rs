@@ -1,12 +1,20 @@
 use super::super::Cmd;
 use super::super::LineState;
 
-pub fn block(cmds: &Vec<Cmd>, state: &mut LineState) {
-    cmds.iter().for_each(|cmd| cmd.eval(state));
+pub fn block(cmds: &[Cmd], state: &mut LineState) {
+    for cmd in cmds {
+        if state.done_with_line {
+            return;
+        }
+        cmd.eval(state);
+    }
 }
 
-pub fn repeat(state: &mut LineState, times: usize, cmd: &Box<Cmd>) {
+pub fn repeat(state: &mut LineState, times: usize, cmd: &Cmd) {
     for _ in 0..times {
+        if state.done_with_line {
+            return;
+        }
         cmd.eval(state);
     }
 }
@@ -33,7 +41,15 @@ mod tests {
         let mut state = LineState::new(SAMPLE_NAV);
         assert_eq!(0, state.cursor, "|^navigation");
 
-        repeat(&mut state, 5, &Box::new(Cmd::Forward));
+        repeat(&mut state, 5, &Cmd::Forward);
         assert_eq!(5, state.cursor, "^navig|ation");
     }
+
+    #[test]
+    fn stop_when_done_with_line() {
+        let mut state = LineState::new(SAMPLE_NAV);
+        let cmds = vec![Cmd::Forward, Cmd::FindNext { what: 'x' }, Cmd::Forward];
+        repeat(&mut state, 2, &Cmd::Block(cmds));
+        assert_eq!(1, state.cursor, "n|avigation");
+    }
 }
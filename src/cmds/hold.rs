@@ -0,0 +1,66 @@
+use super::super::LineState;
+
+/// Moves the cursor to the start of a line that was replaced.
+fn replaced(state: &mut LineState) {
+    state.cursor = 0;
+    state.mark = None;
+}
+
+/// Copies the line to the hold space, like sed's `h`.
+pub fn hold(state: &mut LineState) {
+    state.hold = state.characters.clone();
+}
+
+/// Appends a newline and the line to the hold space, like sed's `H`.
+pub fn hold_append(state: &mut LineState) {
+    state.hold.push('\n');
+    state.hold.extend_from_slice(&state.characters);
+}
+
+/// Replaces the line with the hold space, like sed's `g`.
+pub fn get(state: &mut LineState) {
+    state.characters = state.hold.clone();
+    replaced(state);
+}
+
+/// Appends a newline and the hold space to the line, like sed's `G`.
+pub fn get_append(state: &mut LineState) {
+    state.characters.push('\n');
+    state.characters.extend_from_slice(&state.hold);
+}
+
+/// Swaps the line and the hold space, like sed's `x`.
+pub fn exchange(state: &mut LineState) {
+    std::mem::swap(&mut state.characters, &mut state.hold);
+    replaced(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_space() {
+        let mut state = LineState::new("one");
+        get_append(&mut state);
+        assert_eq!("one\n", state.text());
+        hold_append(&mut state);
+        assert_eq!("\none\n".chars().collect::<Vec<_>>(), state.hold);
+
+        state.characters = "two".chars().collect();
+        hold(&mut state);
+        hold_append(&mut state);
+        get_append(&mut state);
+        assert_eq!("two\ntwo\ntwo", state.text());
+
+        state.cursor = 2;
+        exchange(&mut state);
+        assert_eq!("|two\ntwo", state.render());
+        assert_eq!("two\ntwo\ntwo".chars().collect::<Vec<_>>(), state.hold);
+
+        hold(&mut state);
+        state.characters.clear();
+        get(&mut state);
+        assert_eq!("two\ntwo", state.text());
+    }
+}
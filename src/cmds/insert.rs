@@ -33,7 +33,7 @@ mod tests {
         assert_eq!(16, state.cursor);
 
         // insert in the middle
-        repeat(&mut state, 3, &Box::new(Cmd::Back));
+        repeat(&mut state, 3, &Cmd::Back);
         insert("def", &mut state);
         assert_eq!("abcnavigationdefabc", state.text());
         assert_eq!(16, state.cursor);
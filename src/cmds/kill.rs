@@ -2,12 +2,18 @@ use super::super::LineState;
 use super::word;
 use std::cmp;
 
+/// Deletes from the cursor to the end of the line. In a multi-line buffer
+/// that is the next newline, and the newline itself if the cursor is on it,
+/// like emacs' `C-k`.
 pub fn kill_line_after(state: &mut LineState) {
-    let len = state.characters.len();
     let pos = state.cursor;
-    let delta = len - pos;
-    state.characters.truncate(state.cursor);
-    state.shift_mark_if_greater(pos, delta);
+    let end = match state.characters[pos..].iter().position(|&c| c == '\n') {
+        Some(0) => pos + 1,
+        Some(newline) => pos + newline,
+        None => state.characters.len(),
+    };
+    state.characters.drain(pos..end);
+    state.shift_mark_if_greater(pos, end - pos);
 }
 
 pub fn truncate_by(state: &mut LineState, amount: usize) {
@@ -16,7 +22,7 @@ pub fn truncate_by(state: &mut LineState, amount: usize) {
 }
 
 pub fn kill_line_before(state: &mut LineState) {
-    let _ = state.characters.drain(0..state.cursor).last();
+    state.characters.drain(0..state.cursor);
     let pos = state.cursor;
     state.shift_mark_if_greater(0, pos);
     state.cursor = 0;
@@ -25,7 +31,7 @@ pub fn kill_line_before(state: &mut LineState) {
 pub fn kill_full_word(state: &mut LineState) {
     let from = word::current_word_start(state);
     let to = word::current_word_end(state);
-    let _ = state.characters.drain(from..to).last();
+    state.characters.drain(from..to);
     state.shift_mark_if_greater(from, to - from);
     state.cursor = from;
 }
@@ -40,7 +46,7 @@ pub fn reverse_kill_word(state: &mut LineState) {
             from
         }
     };
-    let _ = state.characters.drain(from..to).last();
+    state.characters.drain(from..to);
     state.shift_mark_if_greater(from, to - from);
     state.cursor = from;
 }
@@ -48,7 +54,7 @@ pub fn reverse_kill_word(state: &mut LineState) {
 pub fn kill_word(state: &mut LineState) {
     let to = word::current_word_end(state);
     let from = state.cursor;
-    let _ = state.characters.drain(from..to).last();
+    state.characters.drain(from..to);
     state.shift_mark_if_greater(from, to - from);
     state.cursor = from;
 }
@@ -63,8 +69,6 @@ mod tests {
     use super::*;
 
     const SAMPLE: &str = "navigation";
-    const NAV_LAST: usize = 10;
-    const NAV_LAST_CHARP: usize = 9;
 
     #[test]
     fn kill_before_cmd() {
@@ -124,6 +128,19 @@ mod tests {
         // If mark is after left point of deletion it is shifted
     }
 
+    #[test]
+    fn kill_after_stops_at_newline() {
+        let mut state = LineState::new("one\ntwo");
+        forward(&mut state);
+        state.mark = Some(6);
+        kill_line_after(&mut state);
+        assert_eq!("o|\ntw^o", state.render());
+
+        // On the newline, the newline is killed
+        kill_line_after(&mut state);
+        assert_eq!("o|tw^o", state.render());
+    }
+
     #[test]
     fn kill_full_word_cmd() {
         let mut state = LineState::new("");
@@ -5,23 +5,11 @@ pub fn copy_line(state: &mut LineState) {
 }
 
 pub fn downcase_line(state: &mut LineState) {
-    let contents: Vec<char> = state
-        .characters
-        .drain(..)
-        .flat_map(|c| c.to_lowercase())
-        .collect();
-
-    state.characters = contents;
+    state.characters = state.downcase_chars(&state.characters);
 }
 
 pub fn upcase_line(state: &mut LineState) {
-    let contents: Vec<char> = state
-        .characters
-        .drain(..)
-        .flat_map(|c| c.to_uppercase())
-        .collect();
-
-    state.characters = contents;
+    state.characters = state.upcase_chars(&state.characters);
 }
 
 fn first_non_whitespace(state: &LineState) -> Option<usize> {
@@ -41,7 +29,7 @@ fn last_non_whitespace(state: &LineState) -> Option<usize> {
 fn trim_to(state: &mut LineState, left: Option<usize>, right: Option<usize>) {
     if let (Some(left), Some(right)) = (left, right) {
         if left != right {
-            let _ = state.characters.drain(left..right).last();
+            state.characters.drain(left..right);
         }
     } else {
         state.characters.truncate(0);
@@ -63,6 +51,35 @@ pub fn rtrim_line(state: &mut LineState) {
     trim_to(state, left, right);
 }
 
+/// Replaces the first occurrence of `from` in the line with `to`, or every
+/// occurrence if `all` is set. The cursor is left after the last replacement.
+pub fn substitute(state: &mut LineState, from: &str, to: &str, all: bool) {
+    let from: Vec<char> = from.chars().collect();
+    let to: Vec<char> = to.chars().collect();
+    if from.is_empty() {
+        return;
+    }
+
+    let mut pos = 0;
+    while pos + from.len() <= state.characters.len() {
+        if state.characters[pos..pos + from.len()] != from[..] {
+            pos += 1;
+            continue;
+        }
+        state.characters.splice(pos..pos + from.len(), to.iter().cloned());
+        pos += to.len();
+        state.cursor = pos;
+        if !all {
+            break;
+        }
+    }
+
+    if state.mark.is_some_and(|mark| mark > state.characters.len()) {
+        state.mark = Some(state.characters.len());
+    }
+    state.cursor = state.cursor.min(state.characters.len());
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -83,4 +100,17 @@ mod tests {
         trim_line(&mut state3);
         assert_eq!("good bye", state3.text());
     }
+
+    #[test]
+    fn substitute_cmd() {
+        let mut state = LineState::new("foo.foo.foo");
+        substitute(&mut state, "foo", "x", false);
+        assert_eq!("x|.foo.foo", state.render());
+
+        substitute(&mut state, "foo", "barbar", true);
+        assert_eq!("x.barbar.barbar|", state.render());
+
+        substitute(&mut state, "a", "aa", true);
+        assert_eq!("x.baarbaar.baarbaa|r", state.render());
+    }
 }
@@ -1,10 +1,13 @@
 pub mod character;
 pub mod clipboard;
 pub mod control;
+pub mod hold;
 pub mod insert;
 pub mod kill;
 pub mod line;
 pub mod navigation;
+pub mod output;
 pub mod region;
+pub mod register;
 pub mod search;
 pub mod word;
@@ -29,6 +29,51 @@ pub fn back(state: &mut LineState) {
     };
 }
 
+/// Where the line of a multi-line buffer that `pos` is on starts.
+fn start_of_line(state: &LineState, pos: usize) -> usize {
+    state.characters[..pos]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+/// Where the line of a multi-line buffer that `pos` is on ends, at its
+/// newline or the end of the buffer.
+fn end_of_line(state: &LineState, pos: usize) -> usize {
+    state.characters[pos..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(state.characters.len(), |newline| pos + newline)
+}
+
+pub fn line_start(state: &mut LineState) {
+    state.cursor = start_of_line(state, state.cursor);
+}
+
+pub fn line_end(state: &mut LineState) {
+    state.cursor = end_of_line(state, state.cursor);
+}
+
+/// Moves to the same column on the next line, or the end of the next line
+/// if it is shorter. Stays on the last line.
+pub fn next_line(state: &mut LineState) {
+    let column = state.cursor - start_of_line(state, state.cursor);
+    let end = end_of_line(state, state.cursor);
+    if end < state.characters.len() {
+        state.cursor = cmp::min(end + 1 + column, end_of_line(state, end + 1));
+    }
+}
+
+/// Moves to the same column on the previous line, or the end of the
+/// previous line if it is shorter. Stays on the first line.
+pub fn prev_line(state: &mut LineState) {
+    let start = start_of_line(state, state.cursor);
+    if start > 0 {
+        let column = state.cursor - start;
+        state.cursor = cmp::min(start_of_line(state, start - 1) + column, start - 1);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,4 +157,28 @@ mod tests {
         assert_eq!(0, state.cursor, "|navigation^");
     }
 
+
+    #[test]
+    fn line_motions() {
+        let mut state = LineState::new("fn a() {\n    b\n}");
+        state.cursor = 6;
+        next_line(&mut state);
+        assert_eq!(14, state.cursor);
+        next_line(&mut state);
+        assert_eq!(16, state.cursor);
+        next_line(&mut state);
+        assert_eq!(16, state.cursor);
+
+        prev_line(&mut state);
+        assert_eq!(10, state.cursor);
+        line_end(&mut state);
+        assert_eq!(14, state.cursor);
+        line_start(&mut state);
+        assert_eq!(9, state.cursor);
+        prev_line(&mut state);
+        prev_line(&mut state);
+        assert_eq!(0, state.cursor);
+        line_start(&mut state);
+        assert_eq!(0, state.cursor);
+    }
 }
@@ -0,0 +1,108 @@
+use super::super::LineState;
+
+/// Leaves the line out of the output. Lines emitted around it are kept.
+pub fn delete_line(state: &mut LineState) {
+    state.deleted = true;
+}
+
+/// Writes the line as it is now, before the edited line.
+pub fn print(state: &mut LineState) {
+    let line = state.characters.iter().collect();
+    state.emitted.push(line);
+}
+
+/// Writes the top of the clipboard as a line of its own, if there is one.
+pub fn print_clipboard(state: &mut LineState) {
+    if let Some(text) = state.clipboard.last() {
+        let line = text.iter().collect();
+        state.emitted.push(line);
+    }
+}
+
+/// Writes a line before the edited line.
+pub fn emit(what: &str, state: &mut LineState) {
+    state.emitted.push(what.to_owned());
+}
+
+/// Writes a line after the edited line.
+pub fn emit_after(what: &str, state: &mut LineState) {
+    state.appended.push(what.to_owned());
+}
+
+/// Ends the line at the cursor. The text before the cursor is written as a
+/// line of its own and editing goes on with the rest.
+pub fn split_line(state: &mut LineState) {
+    let line = state.characters.drain(..state.cursor).collect();
+    state.emitted.push(line);
+    state.mark = match state.mark {
+        Some(mark) if mark >= state.cursor => Some(mark - state.cursor),
+        _ => None,
+    };
+    state.cursor = 0;
+}
+
+/// Asks for the next input line to be appended to the line, after
+/// `separator`. The program runner answers with `join`.
+pub fn join_next(separator: &str, state: &mut LineState) {
+    state.joining = Some(separator.to_owned());
+}
+
+/// Appends `separator` and the input line `next` to the line.
+pub fn join(separator: &str, next: &str, state: &mut LineState) {
+    state
+        .characters
+        .extend(separator.chars().chain(next.chars()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::clipboard::*;
+    use super::super::line::*;
+    use super::*;
+
+    #[test]
+    fn output_lines() {
+        let mut state = LineState::new("line");
+        assert_eq!(vec!["line"], state.output_lines());
+
+        print(&mut state);
+        upcase_line(&mut state);
+        emit("before", &mut state);
+        emit_after("after", &mut state);
+        assert_eq!(
+            vec!["line", "before", "LINE", "after"],
+            state.output_lines()
+        );
+
+        delete_line(&mut state);
+        assert_eq!(vec!["line", "before", "after"], state.output_lines());
+    }
+
+    #[test]
+    fn split_and_join_lines() {
+        let mut state = LineState::new("key=value");
+        state.cursor = 4;
+        state.mark = Some(6);
+        split_line(&mut state);
+        assert_eq!("|va^lue", state.render());
+        assert_eq!(vec!["key=", "value"], state.output_lines());
+
+        join_next(", ", &mut state);
+        assert_eq!(Some(", ".to_owned()), state.joining);
+        join(", ", "next", &mut state);
+        assert_eq!("value, next", state.text());
+    }
+
+    #[test]
+    fn print_clipboard_cmd() {
+        let mut state = LineState::new("navigation");
+        print_clipboard(&mut state);
+        assert_eq!(vec!["navigation"], state.output_lines());
+
+        mark(&mut state);
+        state.cursor = 3;
+        copy(&mut state);
+        print_clipboard(&mut state);
+        assert_eq!(vec!["nav", "navigation"], state.output_lines());
+    }
+}
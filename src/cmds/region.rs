@@ -22,10 +22,7 @@ pub fn upcase(state: &mut LineState) {
 }
 
 pub fn upcase_sequence(state: &mut LineState, start: usize, end: usize) {
-    let contents: Vec<char> = state.characters[start..end]
-        .iter()
-        .flat_map(|c| c.to_uppercase())
-        .collect();
+    let contents = state.upcase_chars(&state.characters[start..end]);
 
     state.characters.splice(start..end, contents);
 }
@@ -37,10 +34,7 @@ pub fn downcase(state: &mut LineState) {
 }
 
 pub fn downcase_sequence(state: &mut LineState, start: usize, end: usize) {
-    let contents: Vec<char> = state.characters[start..end]
-        .iter()
-        .flat_map(|c| c.to_lowercase())
-        .collect();
+    let contents = state.downcase_chars(&state.characters[start..end]);
 
     state.characters.splice(start..end, contents);
 }
@@ -0,0 +1,52 @@
+use super::super::LineState;
+use super::insert::insert;
+
+/// Stores the top of the clipboard in the register `name`.
+pub fn store_register(name: &str, state: &mut LineState) {
+    if let Some(text) = state.clipboard.last() {
+        state.registers.insert(name.to_owned(), text.clone());
+    }
+}
+
+/// Inserts the contents of the register `name` at the cursor. Unset
+/// registers are empty.
+pub fn insert_register(name: &str, state: &mut LineState) {
+    let text: String = match state.registers.get(name) {
+        Some(text) => text.iter().collect(),
+        None => return,
+    };
+    insert(&text, state);
+}
+
+/// Adds one to the counter `name`.
+pub fn increment(name: &str, state: &mut LineState) {
+    *state.counters.entry(name.to_owned()).or_insert(0) += 1;
+}
+
+/// Inserts the value of the counter `name` at the cursor. Unset counters are 0.
+pub fn insert_counter(name: &str, state: &mut LineState) {
+    let value = state.counters.get(name).cloned().unwrap_or(0);
+    insert(&value.to_string(), state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::line::*;
+    use super::*;
+
+    #[test]
+    fn registers_and_counters() {
+        let mut state = LineState::new("line");
+        insert_register("r", &mut state);
+        insert_counter("n", &mut state);
+        assert_eq!("0|line", state.render());
+
+        copy_line(&mut state);
+        store_register("r", &mut state);
+        insert_register("r", &mut state);
+        increment("n", &mut state);
+        increment("n", &mut state);
+        insert_counter("n", &mut state);
+        assert_eq!("00line2|line", state.render());
+    }
+}
@@ -1,7 +1,19 @@
 use super::super::LineState;
 
 pub fn find(what: char, state: &mut LineState) -> bool {
-    for i in state.cursor..=state.characters.len() {
+    for i in state.cursor..state.characters.len() {
+        if state.characters[i] == what {
+            state.cursor = i;
+            return true;
+        }
+    }
+    false
+}
+
+/// Moves to the next occurence of `what` after the cursor, like vim's `f`.
+/// The cursor does not move if there is none.
+pub fn find_next(what: char, state: &mut LineState) -> bool {
+    for i in state.cursor + 1..state.characters.len() {
         if state.characters[i] == what {
             state.cursor = i;
             return true;
@@ -19,3 +31,24 @@ pub fn rfind(what: char, state: &mut LineState) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_and_rfind() {
+        let mut state = LineState::new("a,b");
+        assert!(find(',', &mut state));
+        assert_eq!(1, state.cursor);
+        assert!(!find(';', &mut state));
+        assert_eq!(1, state.cursor);
+
+        // Nothing is found from the end of the line
+        state.cursor = 3;
+        assert!(!find(',', &mut state));
+        assert!(rfind('a', &mut state));
+        assert_eq!(0, state.cursor);
+        assert!(!rfind('a', &mut state));
+    }
+}
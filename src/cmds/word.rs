@@ -30,10 +30,7 @@ enum WordNavigationState {
 }
 
 fn is_word_separator(c: char) -> bool {
-    match c {
-        ' ' | '\t' | '.' | ',' | ';' | ':' => true,
-        _ => false,
-    }
+    matches!(c, ' ' | '\t' | '.' | ',' | ';' | ':')
 }
 
 pub fn current_word_start(state: &LineState) -> usize {
@@ -90,6 +87,117 @@ pub fn current_word_end(state: &LineState) -> usize {
     pos
 }
 
+/// Swaps the word at or before the cursor with the next word and moves to
+/// the end of both, like emacs' `M-t`. Does nothing without two words.
+pub fn transpose_word(state: &mut LineState) {
+    let chars = &state.characters;
+    let mut start = state.cursor;
+    while start > 0 && is_word_separator(chars[start - 1]) {
+        start -= 1;
+    }
+    while start > 0 && !is_word_separator(chars[start - 1]) {
+        start -= 1;
+    }
+    let skip = |from: usize, separator: bool| {
+        (from..chars.len())
+            .find(|&pos| is_word_separator(chars[pos]) != separator)
+            .unwrap_or(chars.len())
+    };
+    let start = skip(start, true);
+    let first_end = skip(start, false);
+    let second_start = skip(first_end, true);
+    let end = skip(second_start, false);
+    if start == first_end || second_start == end {
+        return;
+    }
+
+    let swapped: Vec<char> = chars[second_start..end]
+        .iter()
+        .chain(&chars[first_end..second_start])
+        .chain(&chars[start..first_end])
+        .cloned()
+        .collect();
+    state.characters.splice(start..end, swapped);
+    state.cursor = end;
+}
+
+#[derive(PartialEq)]
+enum CharClass {
+    Blank,
+    Keyword,
+    Punctuation,
+}
+
+/// Classifies characters the way vim does when it splits a line into words.
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Keyword
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Moves to the start of the next word, where a word is a run of letters,
+/// digits and underscores or a run of other non blank characters, like vim's `w`.
+pub fn forward_word_start(state: &mut LineState) {
+    let len = state.characters.len();
+    let mut pos = state.cursor;
+
+    if pos < len {
+        let class = char_class(state.characters[pos]);
+        while class != CharClass::Blank && pos < len && char_class(state.characters[pos]) == class {
+            pos += 1;
+        }
+    }
+    while pos < len && char_class(state.characters[pos]) == CharClass::Blank {
+        pos += 1;
+    }
+
+    state.cursor = pos;
+}
+
+/// Moves to the start of the word before the cursor, like vim's `b`.
+pub fn back_word_start(state: &mut LineState) {
+    let mut pos = state.cursor;
+
+    while pos > 0 && char_class(state.characters[pos - 1]) == CharClass::Blank {
+        pos -= 1;
+    }
+    if pos > 0 {
+        let class = char_class(state.characters[pos - 1]);
+        while pos > 0 && char_class(state.characters[pos - 1]) == class {
+            pos -= 1;
+        }
+    }
+
+    state.cursor = pos;
+}
+
+/// Selects the word under the cursor, like vim's `iw`, with the cursor at the
+/// start of the word and the mark after it.
+pub fn select_word(state: &mut LineState) {
+    let len = state.characters.len();
+    if len == 0 {
+        return;
+    }
+
+    let pos = if state.at_end() { len - 1 } else { state.cursor };
+    let class = char_class(state.characters[pos]);
+    let mut start = pos;
+    while start > 0 && char_class(state.characters[start - 1]) == class {
+        start -= 1;
+    }
+    let mut end = pos;
+    while end < len && char_class(state.characters[end]) == class {
+        end += 1;
+    }
+
+    state.cursor = start;
+    state.mark = Some(end);
+}
+
 pub fn forward_word(state: &mut LineState) {
     let mut word_state = WordNavigationState::BeforeWord;
     for cursor in state.cursor..state.characters.len() {
@@ -132,9 +240,26 @@ mod tests {
     use super::*;
 
     const SAMPLE_BACK: &str = "   Back, ";
-    const BACK_LEN: usize = 9;
     const BACK_LAST: usize = 9;
 
+    #[test]
+    fn transpose_word_cmd() {
+        let mut state = LineState::new("one two, three");
+        state.cursor = 5;
+        transpose_word(&mut state);
+        assert_eq!("one three, two|", state.render());
+
+        // Between words, the words around the cursor are swapped
+        state = LineState::new("one two");
+        state.cursor = 4;
+        transpose_word(&mut state);
+        assert_eq!("two one|", state.render());
+
+        // Nothing happens without a next word
+        transpose_word(&mut state);
+        assert_eq!("two one|", state.render());
+    }
+
     #[test]
     fn current_word_start_fn() {
         let mut state = LineState::new("Hello my name is.");
@@ -217,4 +342,37 @@ mod tests {
         assert_eq!(BACK_LAST, state.cursor, "   Back, ^|");
     }
 
+    #[test]
+    fn forward_and_back_word_start() {
+        let mut state = LineState::new("let x_1 = foo(bar);");
+
+        let starts: Vec<usize> = (0..7)
+            .map(|_| {
+                forward_word_start(&mut state);
+                state.cursor
+            })
+            .collect();
+        assert_eq!(vec![4, 8, 10, 13, 14, 17, 19], starts);
+
+        let starts: Vec<usize> = (0..7)
+            .map(|_| {
+                back_word_start(&mut state);
+                state.cursor
+            })
+            .collect();
+        assert_eq!(vec![17, 14, 13, 10, 8, 4, 0], starts);
+    }
+
+    #[test]
+    fn select_word_cmd() {
+        let mut state = LineState::new("say foo_bar!");
+        goto(&mut state, 6);
+        select_word(&mut state);
+        assert_eq!((4, Some(11)), (state.cursor, state.mark));
+
+        end(&mut state);
+        select_word(&mut state);
+        assert_eq!((11, Some(12)), (state.cursor, state.mark));
+    }
+
 }
@@ -0,0 +1,330 @@
+//! Interactive step debugger for line editing programs.
+//!
+//! Input lines are edited one at a time while the user steps through the
+//! program, inspects and changes the line state and sets breakpoints on
+//! program lines.
+use cmd;
+use cmd::{Cmd, Execution, LineState, Position, Store};
+use std::cmp;
+use std::collections::BTreeSet;
+use std::io;
+use std::io::{BufRead, Write};
+use std::mem;
+
+const HELP: &str = "\
+step (s)                 Execute one command, entering repeat and blocks.
+next (n)                 Execute the next command in full.
+continue (c)             Run until a breakpoint is reached.
+break (b) LINE           Set a breakpoint on a program line.
+delete (d) LINE          Remove the breakpoint on a program line.
+list (l)                 List the program.
+print (p) [FIELD]        Print the line state or one of cursor, mark and clipboard.
+set cursor|mark N        Change the cursor or the mark, `set mark none` removes it.
+set clipboard TEXT       Replace the top of the clipboard.
+quit (q)                 Stop debugging.
+An empty command repeats the previous one.";
+
+enum Action {
+    Stay,
+    Run,
+    Quit,
+}
+
+pub struct Debugger<'a> {
+    program: &'a [Cmd],
+    /// The program line of each top level command.
+    lines: &'a [usize],
+    commands: &'a mut dyn BufRead,
+    out: &'a mut dyn Write,
+    breakpoints: BTreeSet<usize>,
+    previous: String,
+    running: bool,
+    quit: bool,
+}
+
+impl<'a> Debugger<'a> {
+    /// A debugger reading its commands from `commands` and writing to `out`.
+    pub fn new(
+        program: &'a [Cmd],
+        lines: &'a [usize],
+        commands: &'a mut dyn BufRead,
+        out: &'a mut dyn Write,
+    ) -> Debugger<'a> {
+        Debugger {
+            program,
+            lines,
+            commands,
+            out,
+            breakpoints: BTreeSet::new(),
+            previous: String::new(),
+            running: false,
+            quit: false,
+        }
+    }
+
+    fn program_line(&self, execution: &Execution) -> usize {
+        execution.position().map(|pos| self.lines[pos]).unwrap_or(0)
+    }
+
+    fn at_breakpoint(&self, execution: &Execution) -> bool {
+        execution.depth() == 1 && self.breakpoints.contains(&self.program_line(execution))
+    }
+
+    /// Whether the user quit, which ends the input.
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Shows the output lines of an `at_begin` or `at_end` block.
+    pub fn show_block(&mut self, name: &str, lines: &[String]) -> io::Result<()> {
+        writeln!(self.out, "{}:", name)?;
+        for line in lines {
+            writeln!(self.out, "=> {}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Steps through the program on `line` as `cmd::run_on_input` runs it,
+    /// returning the lines it turns into.
+    pub fn edit(
+        &mut self,
+        line: &str,
+        position: Position,
+        input: &mut dyn FnMut() -> Option<String>,
+        store: &mut Store,
+        ascii_case: bool,
+    ) -> io::Result<Vec<String>> {
+        let mut state = LineState::at_position(line, position);
+        state.ascii_case = ascii_case;
+        state.load_store(mem::take(store));
+        writeln!(self.out, "line {}: {}", state.position.line, state.render())?;
+        let mut execution = Execution::new(self.program);
+
+        while !execution.is_done() && !self.quit {
+            if self.running {
+                if !self.at_breakpoint(&execution) {
+                    execution.next(&mut state);
+                    cmd::join_input(&mut state, input);
+                    continue;
+                }
+                writeln!(self.out, "breakpoint at program line {}", self.program_line(&execution))?;
+                self.running = false;
+            }
+
+            match self.prompt(&mut execution, &mut state, input)? {
+                Action::Stay => {}
+                Action::Run => {
+                    self.running = true;
+                    execution.next(&mut state);
+                    cmd::join_input(&mut state, input);
+                }
+                Action::Quit => self.quit = true,
+            }
+        }
+
+        *store = state.take_store();
+        if self.quit {
+            return Ok(Vec::new());
+        }
+        let lines = state.output_lines();
+        for line in &lines {
+            writeln!(self.out, "=> {}", line)?;
+        }
+        Ok(lines)
+    }
+
+    fn prompt(
+        &mut self,
+        execution: &mut Execution,
+        state: &mut LineState,
+        input: &mut dyn FnMut() -> Option<String>,
+    ) -> io::Result<Action> {
+        if let Some(cmd) = execution.current() {
+            writeln!(
+                self.out,
+                "{:>4}: {}{:?}",
+                self.program_line(execution),
+                "  ".repeat(execution.depth() - 1),
+                cmd
+            )?;
+        }
+        write!(self.out, "(lined) ")?;
+        self.out.flush()?;
+
+        let mut command = String::new();
+        if self.commands.read_line(&mut command)? == 0 {
+            return Ok(Action::Quit);
+        }
+        let command = match command.trim() {
+            "" => self.previous.clone(),
+            command => command.to_owned(),
+        };
+        self.previous = command.clone();
+
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("s"), None) | (Some("step"), None) => {
+                if let Some(cmd) = execution.step(state) {
+                    cmd::join_input(state, input);
+                    writeln!(self.out, "{:?} => {}", cmd, state.render())?;
+                }
+            }
+            (Some("n"), None) | (Some("next"), None) => {
+                if let Some(cmd) = execution.next(state) {
+                    cmd::join_input(state, input);
+                    writeln!(self.out, "{:?} => {}", cmd, state.render())?;
+                }
+            }
+            (Some("c"), None) | (Some("continue"), None) => return Ok(Action::Run),
+            (Some("q"), None) | (Some("quit"), None) => return Ok(Action::Quit),
+            (Some("b"), Some(line)) | (Some("break"), Some(line)) => match line.parse() {
+                Ok(line) if self.lines.contains(&line) => {
+                    self.breakpoints.insert(line);
+                }
+                Ok(line) => writeln!(self.out, "No command starts on program line {}.", line)?,
+                Err(_) => writeln!(self.out, "Not a program line: {}.", line)?,
+            },
+            (Some("d"), Some(line)) | (Some("delete"), Some(line)) => match line.parse() {
+                Ok(line) => {
+                    self.breakpoints.remove(&line);
+                }
+                Err(_) => writeln!(self.out, "Not a program line: {}.", line)?,
+            },
+            (Some("l"), None) | (Some("list"), None) => self.list(execution)?,
+            (Some("p"), field) | (Some("print"), field) => print(state, field, self.out)?,
+            (Some("set"), Some(field)) => {
+                let value = command.splitn(3, ' ').nth(2).unwrap_or("");
+                set(state, field, value, self.out)?;
+            }
+            _ => writeln!(self.out, "{}", HELP)?,
+        }
+
+        Ok(Action::Stay)
+    }
+
+    fn list(&mut self, execution: &Execution) -> io::Result<()> {
+        for (pos, cmd) in self.program.iter().enumerate() {
+            let line = self.lines[pos];
+            writeln!(
+                self.out,
+                "{}{} {:>4}: {:?}",
+                if execution.position() == Some(pos) { '>' } else { ' ' },
+                if self.breakpoints.contains(&line) { '*' } else { ' ' },
+                line,
+                cmd
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn clipboard_text(text: &[char]) -> String {
+    text.iter().collect()
+}
+
+fn print(state: &LineState, field: Option<&str>, out: &mut dyn Write) -> io::Result<()> {
+    match field {
+        None => {
+            writeln!(out, "{}", state.render())?;
+            writeln!(out, "cursor = {}, mark = {:?}", state.cursor, state.mark)?;
+            print(state, Some("clipboard"), out)?;
+        }
+        Some("cursor") => writeln!(out, "cursor = {}", state.cursor)?,
+        Some("mark") => writeln!(out, "mark = {:?}", state.mark)?,
+        Some("clipboard") => {
+            for (depth, text) in state.clipboard.iter().rev().enumerate() {
+                writeln!(out, "clipboard[{}] = {:?}", depth, clipboard_text(text))?;
+            }
+        }
+        Some(field) => writeln!(out, "Unknown field {}.", field)?,
+    }
+    Ok(())
+}
+
+fn set(state: &mut LineState, field: &str, value: &str, out: &mut dyn Write) -> io::Result<()> {
+    match field {
+        "mark" if value == "none" => state.mark = None,
+        "cursor" | "mark" => {
+            let position = match value.parse() {
+                Ok(position) => cmp::min(position, state.last_cursor_position()),
+                Err(_) => {
+                    writeln!(out, "Not a position: {}.", value)?;
+                    return Ok(());
+                }
+            };
+            if field == "cursor" {
+                state.cursor = position;
+            } else {
+                state.mark = Some(position);
+            }
+        }
+        "clipboard" => {
+            state.clipboard.pop();
+            state.clipboard.push(value.chars().collect());
+        }
+        _ => {
+            writeln!(out, "Unknown field {}.", field)?;
+            return Ok(());
+        }
+    }
+    writeln!(out, "{}", state.render())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {debug_stream, load_located_script, Dialect, Options, ProgramLocation};
+
+    fn debug(program: &str, input: &str, commands: &str) -> String {
+        let location = ProgramLocation::Text(program.to_owned());
+        let (script, lines) = load_located_script(&location, Dialect::Lined).unwrap();
+        let mut out = Vec::new();
+        let mut commands = commands.as_bytes();
+        let options = Options::default();
+        debug_stream(&script, &lines, input.as_bytes(), &mut commands, &mut out, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_continue_to_breakpoint() {
+        let out = debug("forward\nmark\nend", "ab\ncd\n", "b 3\ns\nc\nc\n\n");
+
+        assert!(out.contains("Forward => a|b"));
+        assert!(out.contains("breakpoint at program line 3"));
+        assert!(out.contains("=> ab\nline 2: |cd"));
+        assert!(out.contains("=> cd\n"));
+    }
+
+    #[test]
+    fn set_state_fields() {
+        let out = debug("cut\npaste\npaste", "abcd\n", "set cursor 3\nset mark 1\nn\nset clipboard xy\nc\n");
+
+        assert!(out.contains("Cut => a|^d"));
+        assert!(out.contains("=> axyxyd"));
+    }
+
+    #[test]
+    fn mistyped_commands_reprompt() {
+        let out = debug("forward", "ab\n", "b foo\nd x\nset cursor x\nset mark -1\nc\n");
+
+        assert!(out.contains("Not a program line: foo."));
+        assert!(out.contains("Not a program line: x."));
+        assert!(out.contains("Not a position: x."));
+        assert!(out.contains("Not a position: -1."));
+        assert!(out.contains("=> ab\n"));
+    }
+
+    #[test]
+    fn edit_records_as_a_run_does() {
+        let out = debug("join_next \"+\" if_line 3 upcase_line", "a\nb\nc\nd\n", "c\n");
+        assert!(out.contains("=> a+b\nline 3: |c\n"), "{}", out);
+        assert!(out.contains("=> C+D\n"), "{}", out);
+
+        let program = r#"lines "2,3" at_begin { insert "start" }
+            if_line 3 get hold at_end { get upcase_line }"#;
+        let out = debug(program, "a\nb\nc\nd\n", "c\n");
+        assert!(out.starts_with("at_begin:\n=> start\nline 2: |b\n"), "{}", out);
+        assert!(out.contains("=> b\nline 3: |c\n"), "{}", out);
+        assert!(out.ends_with("=> b\nat_end:\n=> B\n"), "{}", out);
+    }
+}
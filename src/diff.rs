@@ -0,0 +1,291 @@
+//! Line based unified diffs, using Myers' O(ND) difference algorithm.
+use std::fmt::Write;
+
+/// Lines of context around every change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// The shortest sequence of edits turning `old` into `new`.
+fn edits<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len() + new.len());
+    push_edits(old, new, &mut edits);
+    edits
+}
+
+/// Pushes the edits turning `old` into `new`, splitting the problem at the
+/// middle snake of a shortest path so that only linear space is used.
+fn push_edits<T: PartialEq>(old: &[T], new: &[T], edits: &mut Vec<Edit>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    edits.extend(vec![Edit::Keep; prefix]);
+
+    if old.is_empty() || new.is_empty() {
+        edits.extend(vec![Edit::Delete; old.len()]);
+        edits.extend(vec![Edit::Insert; new.len()]);
+    } else {
+        let (start, end) = middle_snake(old, new);
+        push_edits(&old[..start.0], &new[..start.1], edits);
+        edits.extend(vec![Edit::Keep; end.0 - start.0]);
+        push_edits(&old[end.0..], &new[end.1..], edits);
+    }
+    edits.extend(vec![Edit::Keep; suffix]);
+}
+
+/// The start and end of the diagonal run in the middle of a shortest path
+/// from the start of `old` and `new` to their ends. Both must not be empty
+/// and must differ in their first and last elements.
+fn middle_snake<T: PartialEq>(old: &[T], new: &[T]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    // `forward[k + max]` is the furthest x reached from the start on diagonal
+    // k = x - y, `backward[k + max]` the same from the end of both sequences.
+    let mut forward = vec![0isize; 2 * max as usize + 1];
+    let mut backward = vec![0isize; 2 * max as usize + 1];
+    let at = |k: isize| (k + max) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+            let reverse = delta - k;
+            if delta % 2 != 0 && -d < reverse && reverse < d && x + backward[at(reverse)] >= n {
+                let start = (start.0 as usize, start.1 as usize);
+                return (start, (x as usize, (x - k) as usize));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let end = (n - x, m - x + k);
+            while x < n && x - k < m && old[(n - x - 1) as usize] == new[(m - x + k - 1) as usize] {
+                x += 1;
+            }
+            backward[at(k)] = x;
+            let reverse = delta - k;
+            if delta % 2 == 0 && -d <= reverse && reverse <= d && x + forward[at(reverse)] >= n {
+                let start = ((n - x) as usize, (m - x + k) as usize);
+                return (start, (end.0 as usize, end.1 as usize));
+            }
+        }
+    }
+    unreachable!("a path through the edit graph has at most n + m edits")
+}
+
+/// A hunk range, `start,count` with the count left out when it is 1. Empty
+/// ranges start at the line before them.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// A unified diff between the lines `old` and `new` of the file `name`, empty
+/// if they are equal.
+pub fn unified(name: &str, old: &[&str], new: &[&str]) -> String {
+    // Every edit, with the line it applies to in `old` and `new`.
+    let mut lines = Vec::new();
+    let (mut o, mut n) = (0, 0);
+    for edit in edits(old, new) {
+        lines.push((edit, o, n));
+        match edit {
+            Edit::Keep => {
+                o += 1;
+                n += 1
+            }
+            Edit::Delete => o += 1,
+            Edit::Insert => n += 1,
+        }
+    }
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].0 != Edit::Keep)
+        .collect();
+
+    let mut diff = String::new();
+    if changes.is_empty() {
+        return diff;
+    }
+    let _ = writeln!(diff, "--- {}\n+++ {}", name, name);
+
+    let mut next = 0;
+    while next < changes.len() {
+        let start = changes[next].saturating_sub(CONTEXT);
+        let mut end = changes[next] + CONTEXT;
+        // As in GNU diff, changes with up to twice the context between them
+        // share a hunk.
+        while next + 1 < changes.len() && changes[next + 1] <= end + CONTEXT + 1 {
+            next += 1;
+            end = changes[next] + CONTEXT;
+        }
+        next += 1;
+        let hunk = &lines[start..=end.min(lines.len() - 1)];
+
+        let old_count = hunk.iter().filter(|line| line.0 != Edit::Insert).count();
+        let new_count = hunk.iter().filter(|line| line.0 != Edit::Delete).count();
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            range(hunk[0].1, old_count),
+            range(hunk[0].2, new_count)
+        );
+        for &(edit, o, n) in hunk {
+            let _ = match edit {
+                Edit::Keep => writeln!(diff, " {}", old[o]),
+                Edit::Delete => writeln!(diff, "-{}", old[o]),
+                Edit::Insert => writeln!(diff, "+{}", new[n]),
+            };
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortest_edit_script() {
+        let old: Vec<char> = "ABCABBA".chars().collect();
+        let new: Vec<char> = "CBABAC".chars().collect();
+        let script = edits(&old, &new);
+
+        assert_eq!(5, script.iter().filter(|&&edit| edit != Edit::Keep).count());
+        assert_eq!(vec![Edit::Keep; 2], edits(&old[..2], &old[..2]));
+        assert_eq!(vec![Edit::Insert], edits(&[], &['a']));
+    }
+
+    #[test]
+    fn shortest_edit_scripts_of_small_inputs() {
+        // Every pair of sequences of up to 5 zeros and ones.
+        let sequences: Vec<Vec<u8>> = (0..6)
+            .flat_map(|len| {
+                (0..1 << len).map(move |bits| (0..len).map(|i| bits >> i & 1).collect())
+            })
+            .collect();
+        for old in &sequences {
+            for new in &sequences {
+                let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+                for i in (0..old.len()).rev() {
+                    for j in (0..new.len()).rev() {
+                        common[i][j] = if old[i] == new[j] {
+                            common[i + 1][j + 1] + 1
+                        } else {
+                            common[i + 1][j].max(common[i][j + 1])
+                        };
+                    }
+                }
+
+                let script = edits(old, new);
+                let kept = script.iter().filter(|&&edit| edit == Edit::Keep).count();
+                assert_eq!(common[0][0], kept);
+
+                let (mut o, mut n, mut edited) = (0, 0, Vec::new());
+                for edit in script {
+                    match edit {
+                        Edit::Keep => {
+                            assert_eq!(old[o], new[n]);
+                            edited.push(old[o]);
+                            o += 1;
+                            n += 1;
+                        }
+                        Edit::Delete => o += 1,
+                        Edit::Insert => {
+                            edited.push(new[n]);
+                            n += 1;
+                        }
+                    }
+                }
+                assert_eq!((old.len(), new), (o, &edited));
+            }
+        }
+    }
+
+    #[test]
+    fn every_line_changes() {
+        let old: Vec<usize> = (0..5_000).collect();
+        let new: Vec<usize> = (5_000..10_000).collect();
+        let script = edits(&old, &new);
+
+        assert_eq!(10_000, script.len());
+        assert!(script.iter().all(|&edit| edit != Edit::Keep));
+    }
+
+    #[test]
+    fn unified_hunks() {
+        let old: Vec<String> = (1..=12).map(|n| n.to_string()).collect();
+        let old: Vec<&str> = old.iter().map(|line| line.as_ref()).collect();
+        let mut new = old.clone();
+        new[1] = "two";
+        new.remove(10);
+
+        assert_eq!("", unified("n.txt", &old, &old));
+        assert_eq!(
+            "--- n.txt
++++ n.txt
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -8,5 +8,4 @@
+ 8
+ 9
+ 10
+-11
+ 12
+",
+            unified("n.txt", &old, &new)
+        );
+        assert_eq!(
+            "--- e\n+++ e\n@@ -0,0 +1 @@\n+x\n",
+            unified("e", &[], &["x"])
+        );
+    }
+
+    #[test]
+    fn hunks_merge_up_to_twice_the_context_apart() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let old: Vec<&str> = old.iter().map(|line| line.as_ref()).collect();
+        let headers = |changed: usize| {
+            let mut new = old.clone();
+            new[2] = "x";
+            new[changed - 1] = "y";
+            let diff = unified("n", &old, &new);
+            diff.lines()
+                .filter(|line| line.starts_with("@@"))
+                .map(|line| line.to_owned())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(vec!["@@ -1,13 +1,13 @@"], headers(10));
+        assert_eq!(vec!["@@ -1,6 +1,6 @@", "@@ -8,7 +8,7 @@"], headers(11));
+    }
+}
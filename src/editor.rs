@@ -0,0 +1,136 @@
+use cmd;
+use cmd::{Cmd, Position, Store};
+use std::collections::VecDeque;
+
+/// Lazily applies a program to every line yielded by the wrapped iterator,
+/// yielding the lines each of them turns into. The store is shared by all
+/// lines, and lines are numbered from 1 as if read from stdin.
+pub struct Editor<'a, I> {
+    lines: I,
+    program: &'a [Cmd],
+    pending: VecDeque<String>,
+    store: Store,
+    line: usize,
+}
+
+impl<'a, I, S> Iterator for Editor<'a, I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while self.pending.is_empty() {
+            let line = self.lines.next()?;
+            self.line += 1;
+            let position = Position {
+                line: self.line,
+                file_line: self.line,
+                ..Position::default()
+            };
+            let lines = &mut self.lines;
+            let mut joined = 0;
+            let mut input = || {
+                let line = lines.next()?;
+                joined += 1;
+                Some(line.as_ref().to_owned())
+            };
+            self.pending.extend(cmd::run_on_input(
+                self.program,
+                line.as_ref(),
+                position,
+                &mut input,
+                &mut self.store,
+                false,
+            ));
+            self.line += joined;
+        }
+        self.pending.pop_front()
+    }
+}
+
+pub trait EditTrait<'a, I>: Sized {
+    fn edit_with(self, program: &'a [Cmd]) -> Editor<'a, I>;
+}
+
+impl<'a, I, S> EditTrait<'a, I> for I
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    fn edit_with(self, program: &'a [Cmd]) -> Editor<'a, I> {
+        Editor {
+            lines: self,
+            program,
+            pending: VecDeque::new(),
+            store: Store::default(),
+            line: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser;
+    use tokenizer::TokenizerTrait;
+
+    fn parse(program: &str) -> Vec<Cmd> {
+        parser::parse(&mut program.chars().tokens()).unwrap()
+    }
+
+    #[test]
+    fn edit_with_is_lazy() {
+        let program = vec![Cmd::UpcaseLine];
+        let mut consumed = 0;
+
+        let mut lines = vec!["one", "two", "three"]
+            .into_iter()
+            .inspect(|_| consumed += 1)
+            .edit_with(&program);
+
+        assert_eq!(Some("ONE".to_owned()), lines.next());
+        assert_eq!(Some("TWO".to_owned()), lines.next());
+        drop(lines);
+        assert_eq!(2, consumed);
+    }
+
+    #[test]
+    fn edit_with_owned_strings() {
+        let program = vec![Cmd::End, Cmd::Insert { what: ";".to_owned() }];
+        let edited: Vec<String> = vec!["a".to_owned(), "b".to_owned()]
+            .into_iter()
+            .edit_with(&program)
+            .collect();
+
+        assert_eq!(vec!["a;", "b;"], edited);
+    }
+
+    #[test]
+    fn edit_with_deleted_and_emitted_lines() {
+        let program = vec![Cmd::Print, Cmd::UpcaseLine];
+        let edited: Vec<String> = vec!["a", "b"].into_iter().edit_with(&program).collect();
+        assert_eq!(vec!["a", "A", "b", "B"], edited);
+
+        let program = vec![Cmd::JoinNext {
+            separator: "+".to_owned(),
+        }];
+        let edited: Vec<String> = vec!["a", "b", "c"].into_iter().edit_with(&program).collect();
+        assert_eq!(vec!["a+b", "c"], edited);
+
+        let program = vec![Cmd::DeleteLine];
+        assert_eq!(None, vec!["a", "b"].into_iter().edit_with(&program).next());
+    }
+
+    #[test]
+    fn edit_with_keeps_hold_space_and_line_numbers() {
+        let program = parse("if_line 3 { end insert \"!\" } get_append hold");
+        let edited: Vec<String> = vec!["a", "b", "c"].into_iter().edit_with(&program).collect();
+        assert_eq!(vec!["a\n", "b\na\n", "c!\nb\na\n"], edited);
+
+        let program = parse("join_next \"+\" if_line 3 upcase_line");
+        let edited: Vec<String> = vec!["a", "b", "c", "d"].into_iter().edit_with(&program).collect();
+        assert_eq!(vec!["a+b", "C+D"], edited);
+    }
+}
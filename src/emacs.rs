@@ -0,0 +1,327 @@
+//! Emacs key bindings for line editing commands, and import of keyboard
+//! macros written in the `edmacro` notation used by `edit-kbd-macro` and `kbd`.
+use cmd::Cmd;
+use std::error::Error;
+use std::fmt;
+
+/// A key press, as emacs names them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    /// A self inserting character.
+    Char(char),
+    /// `C-<char>`, with `C-SPC` as `Ctrl(' ')`.
+    Ctrl(char),
+    /// `M-<char>`.
+    Meta(char),
+    /// `DEL`, the backspace key.
+    Backspace,
+    /// `M-DEL`.
+    MetaBackspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+/// The command bound to `key`, if any.
+pub fn command(key: Key) -> Option<Cmd> {
+    let cmd = match key {
+        Key::Char(c) => Cmd::Insert {
+            what: c.to_string(),
+        },
+        Key::Ctrl('f') | Key::Right => Cmd::Forward,
+        Key::Ctrl('b') | Key::Left => Cmd::Back,
+        Key::Meta('f') => Cmd::ForwardWord,
+        Key::Meta('b') => Cmd::BackWord,
+        Key::Ctrl('a') | Key::Home => Cmd::Home,
+        Key::Ctrl('e') | Key::End => Cmd::End,
+        Key::Ctrl('d') | Key::Delete => Cmd::Delete,
+        Key::Backspace => Cmd::DeleteBefore,
+        Key::Ctrl('t') => Cmd::TransposeCharacter,
+        Key::Ctrl('k') => Cmd::KillLine,
+        Key::Meta('d') => Cmd::KillWord,
+        Key::MetaBackspace => Cmd::RKillWord,
+        Key::Ctrl(' ') | Key::Ctrl('@') => Cmd::Mark,
+        Key::Ctrl('w') => Cmd::Cut,
+        Key::Meta('w') => Cmd::Copy,
+        Key::Ctrl('y') => Cmd::Paste,
+        Key::Meta('u') => Cmd::UpcaseWord,
+        Key::Meta('l') => Cmd::DowncaseWord,
+        Key::Meta('c') => Cmd::SentenceCaseWord,
+        _ => return None,
+    };
+    Some(cmd)
+}
+
+/// The largest count `N*KEY` can repeat a key by, also the largest count of
+/// a vim command.
+pub const MAX_COUNT: usize = 100_000;
+
+/// Why a keyboard macro cannot be imported.
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    /// Keys that have no corresponding command.
+    UnsupportedKeys(Vec<String>),
+    /// An `N*KEY` whose count is larger than `MAX_COUNT`.
+    InvalidCount(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::UnsupportedKeys(keys) => {
+                write!(f, "Unsupported keys in macro: {}", keys.join(", "))
+            }
+            ImportError::InvalidCount(word) => write!(
+                f,
+                "Invalid count in macro: {}, counts go up to {}",
+                word, MAX_COUNT
+            ),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+/// Parses a single key name such as `C-a`, `M-DEL`, `SPC` or `<right>`.
+fn parse_key(name: &str) -> Option<Key> {
+    fn single(text: &str) -> Option<char> {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    let key = match name {
+        "SPC" => Key::Char(' '),
+        "TAB" => Key::Char('\t'),
+        "DEL" | "<backspace>" => Key::Backspace,
+        "M-DEL" | "M-<backspace>" => Key::MetaBackspace,
+        "C-SPC" | "C-@" => Key::Ctrl(' '),
+        "<delete>" | "<deletechar>" => Key::Delete,
+        "<left>" => Key::Left,
+        "<right>" => Key::Right,
+        "<home>" => Key::Home,
+        "<end>" => Key::End,
+        _ if name.starts_with("C-") => Key::Ctrl(single(&name[2..])?),
+        _ if name.starts_with("M-") => Key::Meta(single(&name[2..])?),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// True if `word` is written as a key name rather than as text to insert.
+fn is_key_name(word: &str) -> bool {
+    let named = ["SPC", "TAB", "DEL", "RET", "LFD", "ESC", "NUL"];
+    named.contains(&word)
+        || word.starts_with("C-")
+        || word.starts_with("M-")
+        || word.starts_with("S-")
+        || word.starts_with("s-")
+        || word.starts_with("H-")
+        || word.starts_with("A-")
+        || (word.starts_with('<') && word.ends_with('>') && word.len() > 2)
+}
+
+/// Extracts the key sequence from an `edit-kbd-macro` buffer or a
+/// `(kmacro "...")` form. Anything else is taken to be a key sequence.
+fn key_sequence(text: &str) -> &str {
+    if let Some(start) = text.find("(kmacro \"") {
+        let rest = &text[start + 9..];
+        return &rest[..rest.find('"').unwrap_or(rest.len())];
+    }
+    match text.find("\nMacro:") {
+        Some(start) => &text[start + 7..],
+        None => text,
+    }
+}
+
+/// Translates a keyboard macro in edmacro notation into commands. Words that
+/// are not key names insert their text, `N*KEY` repeats a key.
+pub fn import(text: &str) -> Result<Vec<Cmd>, ImportError> {
+    let mut cmds = Vec::new();
+    let mut unsupported = Vec::new();
+
+    for line in key_sequence(text).lines() {
+        let line = &line[..line.find(";;").unwrap_or(line.len())];
+
+        for word in line.split_whitespace() {
+            let (times, word): (usize, &str) = match word.find('*') {
+                Some(star) if star > 0 && word[..star].chars().all(|c| c.is_ascii_digit()) => {
+                    match word[..star].parse() {
+                        Ok(times) if times <= MAX_COUNT => (times, &word[star + 1..]),
+                        _ => return Err(ImportError::InvalidCount(word.to_owned())),
+                    }
+                }
+                _ => (1, word),
+            };
+
+            let keys: Vec<Key> = if is_key_name(word) {
+                parse_key(word).into_iter().collect()
+            } else {
+                word.chars().map(Key::Char).collect()
+            };
+            let word_cmds = compact(keys.into_iter().filter_map(command).collect());
+            if word_cmds.is_empty() {
+                unsupported.push(word.to_owned());
+                continue;
+            }
+
+            cmds.extend(word_cmds.into_iter().map(|cmd| match cmd {
+                _ if times == 1 => cmd,
+                Cmd::Insert { what } => Cmd::Insert {
+                    what: what.repeat(times),
+                },
+                cmd => Cmd::Repeat {
+                    times,
+                    cmd: Box::new(cmd),
+                },
+            }));
+        }
+    }
+
+    if unsupported.is_empty() {
+        Ok(compact(cmds))
+    } else {
+        Err(ImportError::UnsupportedKeys(unsupported))
+    }
+}
+
+/// Merges adjacent inserts into one and runs of the same command into `repeat`.
+pub fn compact(cmds: Vec<Cmd>) -> Vec<Cmd> {
+    let mut compacted: Vec<Cmd> = Vec::new();
+    for cmd in cmds {
+        let merged = match (compacted.last_mut(), &cmd) {
+            (Some(Cmd::Insert { what }), Cmd::Insert { what: more }) => {
+                what.push_str(more);
+                true
+            }
+            (Some(Cmd::Repeat { times, cmd: repeated }), cmd) if **repeated == *cmd => {
+                *times += 1;
+                true
+            }
+            (Some(Cmd::Repeat { times, cmd: repeated }), Cmd::Repeat { times: more, cmd: other })
+                if repeated == other =>
+            {
+                *times += more;
+                true
+            }
+            (Some(previous), cmd) if *previous == *cmd => {
+                let previous = compacted.pop().unwrap();
+                compacted.push(Cmd::Repeat {
+                    times: 2,
+                    cmd: Box::new(previous),
+                });
+                true
+            }
+            _ => false,
+        };
+        if !merged {
+            compacted.push(cmd);
+        }
+    }
+    compacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_edit_kbd_macro_buffer() {
+        let text = ";; Keyboard Macro Editor.  Press C-c C-c to finish.
+;; Original keys: C-a M-f C-k
+
+Command: last-kbd-macro
+Key: none
+
+Macro:
+
+C-a\t\t\t;; move-beginning-of-line
+2*M-f\t\t\t;; forward-word
+const SPC KW_
+C-k\t\t\t;; kill-line
+M-u
+";
+        assert_eq!(
+            Ok(vec![
+                Cmd::Home,
+                Cmd::Repeat {
+                    times: 2,
+                    cmd: Box::new(Cmd::ForwardWord),
+                },
+                Cmd::Insert {
+                    what: "const KW_".to_owned(),
+                },
+                Cmd::KillLine,
+                Cmd::UpcaseWord,
+            ]),
+            import(text)
+        );
+    }
+
+    #[test]
+    fn import_reports_unsupported_keys() {
+        assert_eq!(
+            Err(ImportError::UnsupportedKeys(vec![
+                "C-x".to_owned(),
+                "RET".to_owned(),
+                "<f5>".to_owned()
+            ])),
+            import("(defalias 'foo (kmacro \"C-a C-x RET <f5> M-DEL\"))")
+        );
+    }
+
+    #[test]
+    fn import_counts() {
+        assert_eq!(
+            Ok(vec![
+                Cmd::Repeat {
+                    times: 100_001,
+                    cmd: Box::new(Cmd::Forward),
+                },
+                Cmd::Insert {
+                    what: "ababab".to_owned(),
+                },
+            ]),
+            import("100000*C-f C-f 3*ab")
+        );
+        assert_eq!(
+            Err(ImportError::InvalidCount("99999999999999999999999*C-f".to_owned())),
+            import("99999999999999999999999*C-f")
+        );
+        assert_eq!(
+            Err(ImportError::InvalidCount("100001*C-f".to_owned())),
+            import("100001*C-f")
+        );
+    }
+
+    #[test]
+    fn compact_cmds() {
+        let keys = vec![
+            Key::Ctrl('f'),
+            Key::Right,
+            Key::Ctrl('f'),
+            Key::Char('a'),
+            Key::Char('b'),
+            Key::Meta('f'),
+        ];
+        let cmds = keys.into_iter().filter_map(command).collect();
+
+        assert_eq!(
+            vec![
+                Cmd::Repeat {
+                    times: 3,
+                    cmd: Box::new(Cmd::Forward),
+                },
+                Cmd::Insert {
+                    what: "ab".to_owned(),
+                },
+                Cmd::ForwardWord,
+            ],
+            compact(cmds)
+        );
+    }
+}
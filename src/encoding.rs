@@ -0,0 +1,282 @@
+//! Decoding input that is not valid UTF-8 or in another encoding, and
+//! encoding it back.
+use encoding_rs::{CoderResult, Decoder, DecoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::borrow::Cow;
+use std::char;
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Read};
+use std::str::FromStr;
+
+/// What to do with input that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Decoding {
+    /// Fail with an error.
+    #[default]
+    Utf8,
+    /// Replace invalid sequences with U+FFFD.
+    Lossy,
+    /// Leave records that are not valid UTF-8 unedited and write them back
+    /// as they were.
+    PassThrough,
+    /// Edit every record byte by byte, valid UTF-8 or not. Case changes
+    /// only affect ASCII letters.
+    Bytes,
+}
+
+/// Bytes from 0x80 up are edited as the characters U+10FF80 to U+10FFFF, in
+/// a private use area, so that they have no case and are neither letters nor
+/// whitespace. These characters are written as the bytes they stand for,
+/// wherever they come from.
+const RAW: u32 = 0x10_FF00;
+
+fn raw(byte: u8) -> char {
+    if byte < 0x80 {
+        char::from(byte)
+    } else {
+        char::from_u32(RAW + u32::from(byte)).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+}
+
+/// Decodes `bytes` as one character per byte.
+pub fn from_bytes(bytes: &[u8]) -> String {
+    bytes.iter().cloned().map(raw).collect()
+}
+
+/// Encodes text decoded with `from_bytes` back to the bytes it came from.
+/// Other characters, such as those a program inserts, are written as UTF-8,
+/// except for U+10FF80 to U+10FFFF, which stay single bytes.
+pub fn to_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c as u32 {
+            code if code >= RAW + 0x80 => bytes.push((code - RAW) as u8),
+            _ => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+impl Decoding {
+    /// Decodes `bytes`, returning whether they were decoded byte by byte.
+    pub fn decode(self, bytes: Vec<u8>) -> io::Result<(String, bool)> {
+        if self == Decoding::Bytes {
+            return Ok((from_bytes(&bytes), true));
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok((text, false)),
+            Err(err) => match self {
+                Decoding::Lossy => {
+                    Ok((String::from_utf8_lossy(err.as_bytes()).into_owned(), false))
+                }
+                Decoding::PassThrough => Ok((from_bytes(err.as_bytes()), true)),
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            },
+        }
+    }
+}
+
+/// A character encoding input is read in or output written in, such as
+/// `windows-1252` or `utf-16le`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Charset(&'static Encoding);
+
+impl Charset {
+    /// Whether a byte order mark can be written in the charset.
+    pub fn has_bom(self) -> bool {
+        self.0 == UTF_8 || self.0 == UTF_16LE || self.0 == UTF_16BE
+    }
+
+    /// Reads `input` in the charset as UTF-8, keeping a byte order mark.
+    /// Malformed input is an error, unless `lossy`, which replaces it with
+    /// U+FFFD.
+    pub fn reader<R: BufRead>(self, input: R, lossy: bool) -> DecodeReader<R> {
+        DecodeReader {
+            input,
+            decoder: self.0.new_decoder_without_bom_handling(),
+            lossy,
+            decoded: Vec::new(),
+            read: 0,
+            malformed: false,
+            done: false,
+        }
+    }
+
+    /// Encodes `text`, failing on characters the charset does not have.
+    pub fn encode(self, text: &str) -> io::Result<Vec<u8>> {
+        // encoding_rs only decodes UTF-16.
+        if self.0 == UTF_16LE {
+            return Ok(text
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes().to_vec())
+                .collect());
+        } else if self.0 == UTF_16BE {
+            return Ok(text
+                .encode_utf16()
+                .flat_map(|unit| unit.to_be_bytes().to_vec())
+                .collect());
+        }
+        let (bytes, _, unmappable) = self.0.encode(text);
+        if unmappable {
+            let c = text
+                .chars()
+                .find(|c| self.0.encode(&c.to_string()).2)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            let message = format!("{:?} cannot be encoded in {}", c, self);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(bytes.into_owned())
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.name())
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Charset, String> {
+        Encoding::for_label(label.as_bytes())
+            .map(Charset)
+            .ok_or_else(|| format!("Unknown encoding: {}", label))
+    }
+}
+
+/// Decodes `bytes` in `charset`, or as UTF-8 if none is given, replacing
+/// what cannot be decoded.
+pub fn decode(bytes: &[u8], charset: Option<Charset>) -> Cow<'_, str> {
+    match charset {
+        Some(Charset(encoding)) => encoding.decode_without_bom_handling(bytes).0,
+        None => String::from_utf8_lossy(bytes),
+    }
+}
+
+/// Input in a charset read as UTF-8.
+pub struct DecodeReader<R> {
+    input: R,
+    decoder: Decoder,
+    lossy: bool,
+    /// Decoded input, of which `read` bytes have been read.
+    decoded: Vec<u8>,
+    read: usize,
+    /// Malformed input follows what is decoded.
+    malformed: bool,
+    /// The input is at its end.
+    done: bool,
+}
+
+impl<R: BufRead> DecodeReader<R> {
+    fn decode_more(&mut self) -> io::Result<()> {
+        let input = self.input.fill_buf()?;
+        let last = input.is_empty();
+        let (decoded, consumed) = if self.lossy {
+            let length = self.decoder.max_utf8_buffer_length(input.len());
+            let mut decoded = String::with_capacity(length.unwrap_or(4096));
+            let (result, consumed, _) = self.decoder.decode_to_string(input, &mut decoded, last);
+            self.done = last && result == CoderResult::InputEmpty;
+            (decoded, consumed)
+        } else {
+            let length = self
+                .decoder
+                .max_utf8_buffer_length_without_replacement(input.len());
+            let mut decoded = String::with_capacity(length.unwrap_or(4096));
+            let (result, consumed) =
+                self.decoder
+                    .decode_to_string_without_replacement(input, &mut decoded, last);
+            match result {
+                DecoderResult::InputEmpty => self.done = last,
+                DecoderResult::OutputFull => {}
+                DecoderResult::Malformed(..) => self.malformed = true,
+            }
+            (decoded, consumed)
+        };
+        self.input.consume(consumed);
+        self.decoded = decoded.into_bytes();
+        self.read = 0;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.decoded.len() && !self.done {
+            if self.malformed {
+                let message = format!("input is not valid {}", self.decoder.encoding().name());
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            self.decode_more()?;
+        }
+        let count = cmp::min(buf.len(), self.decoded.len() - self.read);
+        buf[..count].copy_from_slice(&self.decoded[self.read..self.read + count]);
+        self.read += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_invalid_utf8() {
+        let bytes = b"caf\xe9 \xc3\xa9".to_vec();
+        let err = Decoding::Utf8.decode(bytes.clone()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let (text, raw) = Decoding::Lossy.decode(bytes.clone()).unwrap();
+        assert_eq!(("caf\u{fffd} é", false), (text.as_str(), raw));
+
+        let (text, raw) = Decoding::Bytes.decode(bytes.clone()).unwrap();
+        assert!(raw);
+        assert_eq!(7, text.chars().count());
+        assert_eq!("CAF", text.to_uppercase().get(..3).unwrap());
+        assert_eq!(bytes, to_bytes(&text));
+        let inserted = from_bytes(b"\xe9!") + "é";
+        assert_eq!(b"\xe9!\xc3\xa9", &to_bytes(&inserted)[..]);
+        assert_eq!(b"\xe9", &to_bytes("\u{10ffe9}")[..]);
+    }
+
+    #[test]
+    fn charsets() {
+        let windows: Charset = "windows-1252".parse().unwrap();
+        let utf16: Charset = "utf-16le".parse().unwrap();
+        assert!("klingon".parse::<Charset>().is_err());
+
+        let mut text = String::new();
+        let input = &b"\x93caf\xe9\x94"[..];
+        windows
+            .reader(input, false)
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!("\u{201c}café\u{201d}", text);
+        assert_eq!(input, &windows.encode(&text).unwrap()[..]);
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            windows.encode("\u{3b1}").unwrap_err().kind()
+        );
+
+        let input = &b"\xff\xfea\x00\n\x00"[..];
+        let mut text = String::new();
+        utf16
+            .reader(input, false)
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!("\u{feff}a\n", text);
+        assert_eq!(input, &utf16.encode(&text).unwrap()[..]);
+        assert!(utf16.has_bom() && !windows.has_bom());
+
+        // An unpaired surrogate, after text that is read before the error.
+        let input = &b"a\x00\x00\xd8"[..];
+        let mut reader = utf16.reader(input, false);
+        let mut buf = [0; 8];
+        assert_eq!(1, reader.read(&mut buf).unwrap());
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let mut text = String::new();
+        utf16.reader(input, true).read_to_string(&mut text).unwrap();
+        assert_eq!("a\u{fffd}", text);
+    }
+}
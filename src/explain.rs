@@ -0,0 +1,295 @@
+//! Plain English descriptions of programs, with a worked example on a sample line.
+use cmd;
+use cmd::{Cmd, Condition, Execution, LineState};
+use std::io;
+use std::io::Write;
+
+fn quoted(text: &str) -> String {
+    format!("{:?}", text)
+}
+
+/// Describes what a command does, in a form that reads well after a step number.
+pub fn describe(cmd: &Cmd) -> String {
+    let text = match cmd {
+        Cmd::Back => "move one character back",
+        Cmd::Forward => "move one character forward",
+        Cmd::ForwardWord => "move to the end of the next word",
+        Cmd::BackWord => "move to the start of the previous word",
+        Cmd::Home => "move to the start of the line",
+        Cmd::End => "move to the end of the line",
+        Cmd::Last => "move to the last character",
+        Cmd::ForwardWordStart => "move to the start of the next word",
+        Cmd::BackWordStart => "move to the start of the word before the cursor",
+        Cmd::ForwardLine => "move to the same column on the next line",
+        Cmd::BackLine => "move to the same column on the previous line",
+        Cmd::LineStart => "move to the start of the current line",
+        Cmd::LineEnd => "move to the end of the current line",
+        Cmd::Delete => "delete the character at the cursor",
+        Cmd::DeleteBefore => "delete the character before the cursor",
+        Cmd::TransposeCharacter => "swap the character before the cursor with the one at it",
+        Cmd::UpcaseCharacter => "upcase the character at the cursor",
+        Cmd::DowncaseCharacter => "downcase the character at the cursor",
+        Cmd::CopyLine => "copy the whole line to the clipboard",
+        Cmd::KillWord => "delete from the cursor to the end of the word",
+        Cmd::RKillWord => "delete from the start of the word to the cursor",
+        Cmd::KillFullWord => "delete the word under the cursor",
+        Cmd::TransposeWord => "swap the word at or before the cursor with the next word",
+        Cmd::UpcaseWord => "upcase the word under the cursor",
+        Cmd::DowncaseWord => "downcase the word under the cursor",
+        Cmd::SentenceCaseWord => "capitalize the word under the cursor",
+        Cmd::Mark => "set the mark at the cursor",
+        Cmd::ExchangeMark => "swap the cursor and the mark",
+        Cmd::SelectWord => "select the word under the cursor",
+        Cmd::UpcaseRegion => "upcase the text between the mark and the cursor",
+        Cmd::DowncaseRegion => "downcase the text between the mark and the cursor",
+        Cmd::Transpose => {
+            "move the text after the character at the cursor to the start of the line"
+        }
+        Cmd::Copy => "copy the text between the mark and the cursor to the clipboard",
+        Cmd::Cut => "cut the text between the mark and the cursor to the clipboard",
+        Cmd::Paste => "paste the clipboard at the cursor",
+        Cmd::PasteAfter => "paste the clipboard after the character at the cursor",
+        Cmd::UpcaseClipboard => "upcase the clipboard",
+        Cmd::DowncaseClipboard => "downcase the clipboard",
+        Cmd::SentencecaseClipboard => "capitalize the clipboard",
+        Cmd::LeftTrimClipboard => "remove leading whitespace from the clipboard",
+        Cmd::RightTrimClipboard => "remove trailing whitespace from the clipboard",
+        Cmd::TrimClipboard => "remove surrounding whitespace from the clipboard",
+        Cmd::Kill | Cmd::KillLine => "delete from the cursor to the end of the line",
+        Cmd::RKillLine => "delete from the start of the line to the cursor",
+        Cmd::TrimLine => "remove surrounding whitespace from the line",
+        Cmd::LTrimLine => "remove leading whitespace from the line",
+        Cmd::RTrimLine => "remove trailing whitespace from the line",
+        Cmd::UpcaseLine => "upcase the whole line",
+        Cmd::DowncaseLine => "downcase the whole line",
+        Cmd::NextLine => "stop editing the line",
+        Cmd::DeleteLine => "leave the line out of the output",
+        Cmd::Print => "print the line as it is now",
+        Cmd::PrintClipboard => "print the clipboard as a line of its own",
+        Cmd::SplitLine => "end the line at the cursor, going on with the rest as a new line",
+        Cmd::JoinNext { separator } if separator.is_empty() => {
+            "append the next input line to the line"
+        }
+        Cmd::Hold => "copy the line to the hold space",
+        Cmd::HoldAppend => "append the line to the hold space on a new line",
+        Cmd::Get => "replace the line with the hold space",
+        Cmd::GetAppend => "append the hold space to the line on a new line",
+        Cmd::Exchange => "swap the line and the hold space",
+        Cmd::StoreRegister { name } => {
+            return format!("store the clipboard in the register {}", quoted(name))
+        }
+        Cmd::InsertRegister { name } => {
+            return format!("insert the register {} at the cursor", quoted(name))
+        }
+        Cmd::Increment { name } => return format!("add one to the counter {}", quoted(name)),
+        Cmd::InsertCounter { name } => {
+            return format!("insert the counter {} at the cursor", quoted(name))
+        }
+        Cmd::JoinNext { separator } => {
+            return format!("append {} and the next input line to the line", quoted(separator))
+        }
+        Cmd::Emit { what } => return format!("print {} before the line", quoted(what)),
+        Cmd::EmitAfter { what } => return format!("print {} after the line", quoted(what)),
+        Cmd::Goto(column) => return format!("move to column {}", column),
+        Cmd::TruncateBy(amount) => {
+            return format!("delete the last {} characters of the line", amount)
+        }
+        Cmd::Find { what } => {
+            return format!(
+                "move to the next {} at or after the cursor",
+                quoted(&what.to_string())
+            )
+        }
+        Cmd::FindNext { what } => {
+            return format!(
+                "move to the next {} after the cursor",
+                quoted(&what.to_string())
+            )
+        }
+        Cmd::RFind { what } => {
+            return format!("move back to the previous {}", quoted(&what.to_string()))
+        }
+        Cmd::Insert { what } => return format!("insert {} at the cursor", quoted(what)),
+        Cmd::InsertTemplate { template } => {
+            return format!("insert {} at the cursor, filling in the position", quoted(template))
+        }
+        Cmd::If { condition, cmd } => {
+            let condition = match condition {
+                Condition::Line(line) => format!("on line {}", line),
+                Condition::FileLine(line) => format!("on line {} of every file", line),
+                Condition::File(file) => format!("in the file {}", quoted(file)),
+            };
+            return format!("{}: {}", condition, describe(cmd));
+        }
+        Cmd::Translate { table } => {
+            let mut pairs: Vec<(&char, &char)> = table.iter().collect();
+            pairs.sort();
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(from, to)| format!("{:?} with {:?}", from, to))
+                .collect();
+            return format!("replace every {}", pairs.join(", "));
+        }
+        Cmd::Substitute { from, to, all } => {
+            let which = if *all { "every" } else { "the first" };
+            return format!("replace {} {} with {}", which, quoted(from), quoted(to));
+        }
+        Cmd::Repeat { times, cmd } => return format!("{} times: {}", times, describe(cmd)),
+        Cmd::Block(cmds) => {
+            let steps: Vec<String> = cmds.iter().map(describe).collect();
+            return steps.join(", then ");
+        }
+    };
+    text.to_owned()
+}
+
+fn show_state(state: &LineState, clipboard_changed: bool) -> String {
+    let mut shown = format!("cursor at {}", state.cursor);
+    if let Some(mark) = state.mark {
+        shown += &format!(", mark at {}", mark);
+    }
+    if clipboard_changed {
+        let text: String = state
+            .clipboard
+            .last()
+            .map_or(String::new(), |text| text.iter().collect());
+        shown += &format!(", clipboard {:?}", text);
+    }
+    format!("{}: `{}`", shown, state.render())
+}
+
+/// Writes one numbered step per top level command of `program`. With a
+/// sample line, every step also shows the line after the command has run, as
+/// `cmd::run` runs it. Steps after the line is done are skipped.
+pub fn explain<W: Write>(program: &[Cmd], sample: Option<&str>, out: &mut W) -> io::Result<()> {
+    let mut state = sample.map(LineState::new);
+    if let Some(state) = &state {
+        writeln!(out, "start → {}", show_state(state, false))?;
+    }
+
+    let mut execution = Execution::new(program);
+    for (step, cmd) in program.iter().enumerate() {
+        write!(out, "{}. {}", step + 1, describe(cmd))?;
+        if let Some(state) = &mut state {
+            if execution.is_done() {
+                write!(out, " → skipped, the line is done")?;
+            } else {
+                let clipboard = state.clipboard.clone();
+                execution.next(state);
+                cmd::join_input(state, &mut || None);
+                write!(
+                    out,
+                    " → {}",
+                    show_state(state, state.clipboard != clipboard)
+                )?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    if let Some(state) = &state {
+        let lines = state.output_lines();
+        if lines.is_empty() {
+            writeln!(out, "result: no lines")?;
+        }
+        for line in lines {
+            writeln!(out, "result: {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Describes an `at_begin` or `at_end` block. Blocks run on an empty line of
+/// their own, so they are not shown on the sample line.
+pub fn explain_block<W: Write>(name: &str, block: &[Cmd], out: &mut W) -> io::Result<()> {
+    if block.is_empty() {
+        return Ok(());
+    }
+    let steps: Vec<String> = block.iter().map(describe).collect();
+    writeln!(out, "{}: {}", name, steps.join(", then "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser;
+    use tokenizer::TokenizerTrait;
+
+    fn explained(program: &str, sample: Option<&str>) -> String {
+        let program = parser::parse(&mut program.chars().tokens()).unwrap();
+        let mut out = Vec::new();
+        explain(&program, sample, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn explain_with_sample() {
+        assert_eq!(
+            "start → cursor at 0: `|    Back,`
+1. set the mark at the cursor → cursor at 0, mark at 0: `|^    Back,`
+2. move to the start of the next word → cursor at 4, mark at 0: `^    |Back,`
+3. cut the text between the mark and the cursor to the clipboard → cursor at 0, mark at 0, clipboard \"    \": `|^Back,`
+4. 2 times: upcase the character at the cursor, then move one character forward → cursor at 2, mark at 0: `^BA|ck,`
+result: BAck,
+",
+            explained("mark forward_word_start cut repeat 2 { upcase_char forward }", Some("    Back,"))
+        );
+    }
+
+    #[test]
+    fn explain_without_sample() {
+        assert_eq!(
+            "1. replace every 'a' with 'x', 'b' with 'y'\n2. insert \"\\\"\" at the cursor\n",
+            explained(r#"translate "ab" "xy" insert "\"""#, None)
+        );
+    }
+
+    #[test]
+    fn explain_stops_when_the_line_is_done() {
+        assert_eq!(
+            "start → cursor at 0: `|abc`
+1. move one character forward → cursor at 1: `a|bc`
+2. stop editing the line → cursor at 1: `a|bc`
+3. upcase the whole line → skipped, the line is done
+result: abc
+",
+            explained("forward nextline upcase_line", Some("abc"))
+        );
+    }
+
+    #[test]
+    fn explain_results_match_a_run() {
+        let programs = [
+            "forward nextline upcase_line",
+            r#"find "x" upcase_line"#,
+            r#"repeat 3 { find_next "a" delete } insert "-""#,
+            r#"print upcase_line join_next "+" emit "e""#,
+            "delete_line",
+        ];
+        for program in &programs {
+            let cmds = parser::parse(&mut program.chars().tokens()).unwrap();
+            let results: Vec<String> = cmd::run(&cmds, "banana")
+                .iter()
+                .map(|line| format!("result: {}\n", line))
+                .collect();
+            let explained = explained(program, Some("banana"));
+            let shown = &explained[explained.find("result: ").unwrap()..];
+            match results.len() {
+                0 => assert_eq!("result: no lines\n", shown, "{}", program),
+                _ => assert_eq!(results.concat(), shown, "{}", program),
+            }
+        }
+    }
+
+    #[test]
+    fn explain_blocks() {
+        let block = vec![Cmd::Insert { what: "x".to_owned() }, Cmd::UpcaseLine];
+        let mut out = Vec::new();
+        explain_block("at_end", &block, &mut out).unwrap();
+        explain_block("at_begin", &[], &mut out).unwrap();
+        assert_eq!(
+            "at_end: insert \"x\" at the cursor, then upcase the whole line\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
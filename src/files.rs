@@ -0,0 +1,128 @@
+//! Editing files in place.
+use std::error::Error;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+use {Options, Progress, Script};
+
+/// A new, empty file next to `path`, so that it can be renamed over `path`.
+fn create_temp_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut attempt = 0;
+    loop {
+        let temp = dir.join(format!(
+            ".{}.lined-{}-{}",
+            name.to_string_lossy(),
+            process::id(),
+            attempt
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn write_edited(
+    script: &Script,
+    path: &Path,
+    progress: &mut Progress,
+    (temp, output): (&Path, File),
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let input = File::open(path)?;
+    let permissions = input.metadata()?.permissions();
+    let name = path.to_string_lossy();
+    let input = BufReader::new(input);
+    ::edit_script(script, input, &output, options, &name, progress)?;
+    output.sync_all()?;
+    fs::set_permissions(temp, permissions)?;
+    Ok(())
+}
+
+/// Replaces the file at `path` with its edited contents. The edited file is
+/// written next to it and renamed over it, so the file is never left half
+/// written. A copy of the original is kept with `backup_suffix` appended to
+/// its name, if given. The `at_begin` and `at_end` blocks of `script` write
+/// to the file as well.
+pub fn edit_in_place(
+    script: &Script,
+    path: &str,
+    progress: &mut Progress,
+    backup_suffix: Option<&str>,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    let (temp, output) = create_temp_file(path)?;
+
+    if let Err(err) = write_edited(script, path, progress, (&temp, output), options) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    if let Some(suffix) = backup_suffix {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        if let Err(err) = fs::copy(path, &backup) {
+            let _ = fs::remove_file(&temp);
+            return Err(err.into());
+        }
+    }
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use {load_script, Dialect, ProgramLocation};
+
+    #[test]
+    fn edit_file_in_place_with_backup() {
+        let dir = env::temp_dir().join(format!("lined-in-place-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("constants.txt");
+        fs::write(&path, "back\nforward\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let text = r#"upcase_line insert_template "{line} " at_end { insert_template "{line} lines" }"#;
+        let script = load_script(&ProgramLocation::Text(text.to_owned()), Dialect::Lined).unwrap();
+        let options = Options::default();
+        let mut progress = Progress::new(&options);
+        progress.lines = 3;
+        let path_text = path.to_str().unwrap();
+        edit_in_place(&script, path_text, &mut progress, Some(".bak"), &options).unwrap();
+
+        assert_eq!(5, progress.lines);
+        let edited = fs::read_to_string(&path).unwrap();
+        assert_eq!("4 BACK\n5 FORWARD\n5 lines\n", edited);
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        let backup = fs::read_to_string(dir.join("constants.txt.bak")).unwrap();
+        assert_eq!("back\nforward\n", backup);
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        let missing = dir.join("missing");
+        let edited = edit_in_place(
+            &script,
+            missing.to_str().unwrap(),
+            &mut progress,
+            None,
+            &options,
+        );
+        assert!(edited.is_err());
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
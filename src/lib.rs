@@ -1,48 +1,1005 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate encoding_rs;
+extern crate glob;
+extern crate regex;
+extern crate serde;
+extern crate serde_json;
+extern crate termion;
+
+use std::fmt;
 use std::fs::File;
 use std::io;
-use std::io::Read;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 
+mod address;
 mod cmd;
 mod cmds;
+mod debugger;
+mod diff;
+mod editor;
+mod emacs;
+mod encoding;
+mod explain;
+mod files;
 mod parser;
+mod recorder;
+mod records;
+mod repl;
+mod sed;
+mod testing;
 mod tokenizer;
-use cmd::{Cmd, LineState};
+mod vim;
+pub use address::{Address, AddressError};
+pub use cmd::{Cmd, LineState};
+pub use editor::{EditTrait, Editor};
+pub use encoding::{Charset, Decoding};
+pub use records::Separator;
 use std::error::Error;
 use tokenizer::*;
 
 pub enum ProgramLocation {
     File(String),
     Text(String),
+    /// A file containing a program with its directives and blocks serialized
+    /// as JSON, as `--emit-ast` prints it.
+    JsonFile(String),
+}
+
+/// The syntax a program is written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    /// The native lined command language.
+    Lined,
+    /// Vim normal mode keys, such as `0f,dw`.
+    Vim,
+}
+
+/// Controls when edited lines are flushed to the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flush {
+    /// Output is buffered and flushed when the input is exhausted.
+    Buffered,
+    /// Output is flushed after every line, for use in interactive pipes.
+    Line,
+}
+
+/// Options controlling how a stream is edited.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub flush: Flush,
+    /// Print every executed command and the resulting line state to stderr.
+    pub trace: bool,
+    /// Only edit the lines selected by the address, passing other lines through.
+    pub address: Option<Address>,
+    /// Carry the clipboard, registers and counters over from line to line.
+    pub persist: bool,
+    /// What ends the records of the input.
+    pub separator: Separator,
+    /// Written after every record instead of the separator that ended it,
+    /// which normalizes line endings.
+    pub output_separator: Option<String>,
+    /// Leave out a byte order mark at the start of the input instead of
+    /// writing it back.
+    pub strip_bom: bool,
+    /// What to do with input that is not valid UTF-8.
+    pub decoding: Decoding,
+    /// The encoding of the input, if not UTF-8.
+    pub input_encoding: Option<Charset>,
+    /// The encoding to write the output in, if not UTF-8.
+    pub output_encoding: Option<Charset>,
+}
+
+impl Options {
+    fn output_separator(&self) -> &str {
+        match &self.output_separator {
+            Some(separator) => separator,
+            None => self.separator.output(),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            flush: Flush::Buffered,
+            trace: false,
+            address: None,
+            persist: false,
+            separator: Separator::default(),
+            output_separator: None,
+            strip_bom: false,
+            decoding: Decoding::default(),
+            input_encoding: None,
+            output_encoding: None,
+        }
+    }
+}
+
+/// Where the edited lines are written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Stdout,
+    /// Every input file is replaced by its edited version, keeping a copy of
+    /// the original with the suffix appended to its name, if given.
+    InPlace { backup_suffix: Option<String> },
+    /// A unified diff of every changed input is printed instead of the
+    /// edited lines. With `check`, editing fails if any input would change.
+    Diff { check: bool },
+    /// Only the names of the inputs that would change are printed, and
+    /// editing fails if there are any.
+    Check,
 }
 
 pub struct Cfg {
     pub program: ProgramLocation,
+    /// The files to edit, stdin if empty.
+    pub files: Vec<String>,
+    pub output: Output,
+    pub dialect: Dialect,
+    pub options: Options,
+    /// Print the parsed program as JSON instead of editing the input.
+    pub emit_ast: bool,
+}
+
+/// An I/O error that occured while editing a stream, together with the
+/// (1-based) number of the input line that was being processed.
+#[derive(Debug)]
+pub struct StreamError {
+    pub line: usize,
+    pub error: io::Error,
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
 }
 
-fn slurp(path: &str) -> Result<String, Box<Error>> {
+fn slurp(path: &str) -> Result<String, Box<dyn Error>> {
     let mut file = File::open(path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
     Ok(contents)
 }
 
-fn get_program_text(loc: &ProgramLocation) -> Result<String, Box<Error>> {
+/// Compiles the text of a line editing program.
+pub fn compile(text: &str) -> Result<Vec<Cmd>, Box<dyn Error>> {
+    let program = parser::parse(&mut text.chars().tokens())?;
+    if program.is_empty() {
+        return Err("Empty program.".into());
+    }
+    Ok(program)
+}
+
+/// Compiles the text of a program written in `dialect`.
+pub fn compile_as(text: &str, dialect: Dialect) -> Result<Vec<Cmd>, Box<dyn Error>> {
+    match dialect {
+        Dialect::Lined => compile(text),
+        Dialect::Vim => {
+            let program = vim::parse(text)?;
+            if program.is_empty() {
+                return Err("Empty program.".into());
+            }
+            Ok(program)
+        }
+    }
+}
+
+/// Serializes a compiled program as pretty printed JSON.
+pub fn to_json(program: &[Cmd]) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(program)?)
+}
+
+/// Deserializes a program previously serialized with `to_json`.
+pub fn from_json(json: &str) -> Result<Vec<Cmd>, Box<dyn Error>> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Loads a program, parsing program text in `dialect`. JSON programs have no dialect.
+pub fn load_program(loc: &ProgramLocation, dialect: Dialect) -> Result<Vec<Cmd>, Box<dyn Error>> {
     match loc {
-        ProgramLocation::Text(text) => Ok(text.to_string()),
-        ProgramLocation::File(path) => slurp(path),
+        ProgramLocation::Text(text) => compile_as(text, dialect),
+        ProgramLocation::File(path) => compile_as(&slurp(path)?, dialect),
+        ProgramLocation::JsonFile(path) => Ok(serde_json::from_str::<Script>(&slurp(path)?)?.cmds),
+    }
+}
+
+/// Runs `program` on every record of `input` and writes the edited records to
+/// `output`. Records are lines unless `options` has another separator. The
+/// separator that ended a record is written after it, so that line endings,
+/// a missing final newline and a byte order mark are kept.
+pub fn run_stream<R: BufRead, W: Write>(
+    program: &[Cmd],
+    input: R,
+    output: W,
+    options: &Options,
+) -> Result<(), StreamError> {
+    edit_stream(program, input, output, options, "-", &mut Progress::new(options))
+}
+
+/// What is written after an output line: the output separator if one is
+/// given, else the separator that ended the input record. Lines a program
+/// adds before the last line of a record that had no separator get the
+/// default separator.
+fn line_ending<'a>(options: &'a Options, ending: &'a Option<String>, last: bool) -> &'a str {
+    match (&options.output_separator, ending) {
+        (Some(separator), _) => separator,
+        (None, Some(ending)) if last || !ending.is_empty() => ending,
+        (None, _) => options.separator.output(),
     }
 }
 
-use std::io::BufRead;
+/// Writes an output line, encoding it back byte by byte if it was decoded so,
+/// or else in `charset` if one is given.
+fn write_line<W: Write>(
+    output: &mut W,
+    line: &str,
+    ending: &str,
+    raw: bool,
+    charset: Option<Charset>,
+) -> io::Result<()> {
+    if raw {
+        output.write_all(&encoding::to_bytes(line))?;
+        output.write_all(&encoding::to_bytes(ending))
+    } else if let Some(charset) = charset {
+        output.write_all(&charset.encode(line)?)?;
+        output.write_all(&charset.encode(ending)?)
+    } else {
+        write!(output, "{}{}", line, ending)
+    }
+}
 
-pub fn run(cfg: &Cfg) -> Result<(), Box<Error>> {
-    let program_text = get_program_text(&cfg.program)?;
-    let program = parser::parse(&mut program_text.chars().tokens()).unwrap();
+/// How far editing has got over all inputs.
+struct Progress<'a> {
+    lines: usize,
+    selection: address::Selection<'a>,
+    /// What every line starts from, updated by every line when persisting.
+    /// The hold space is always updated.
+    store: cmd::Store,
+    /// Steps through the program on the selected lines instead of running it.
+    debugger: Option<&'a mut debugger::Debugger<'a>>,
+}
+
+impl<'a> Progress<'a> {
+    fn new(options: &'a Options) -> Progress<'a> {
+        Progress {
+            lines: 0,
+            selection: address::Selection::new(options.address.as_ref()),
+            store: cmd::Store::default(),
+            debugger: None,
+        }
+    }
+}
+
+/// Same as `run_stream` for the input file `file`, continuing after the
+/// inputs edited so far.
+fn edit_stream<R: BufRead, W: Write>(
+    program: &[Cmd],
+    input: R,
+    output: W,
+    options: &Options,
+    file: &str,
+    progress: &mut Progress,
+) -> Result<(), StreamError> {
+    match options.input_encoding {
+        Some(charset) => {
+            let lossy = options.decoding == Decoding::Lossy;
+            let input = BufReader::new(charset.reader(input, lossy));
+            edit_records(program, input, output, options, file, progress)
+        }
+        None => edit_records(program, input, output, options, file, progress),
+    }
+}
+
+/// Same as `edit_stream` for UTF-8 input.
+fn edit_records<R: BufRead, W: Write>(
+    program: &[Cmd],
+    input: R,
+    output: W,
+    options: &Options,
+    file: &str,
+    progress: &mut Progress,
+) -> Result<(), StreamError> {
+    let mut output = BufWriter::new(output);
+    let stderr = io::stderr();
+    let mut number = 0;
+    let mut lines = records::Records::new(input, &options.separator, options.decoding);
+    let bom = lines.skip_bom().map_err(|error| StreamError { line: 1, error })?;
+    if bom && !options.strip_bom {
+        // A byte order mark is dropped in charsets that have none.
+        match options.output_encoding {
+            Some(charset) if !charset.has_bom() => Ok(()),
+            charset => write_line(&mut output, "\u{feff}", "", false, charset),
+        }
+        .map_err(|error| StreamError { line: 1, error })?;
+    }
+    let mut lines = lines.peekable();
+    // Records that are not valid UTF-8 are passed through unedited.
+    let passed = |record: &records::Record| {
+        record.raw && options.decoding == Decoding::PassThrough
+    };
+    let bytes = options.decoding == Decoding::Bytes;
+
+    while let Some(record) = lines.next() {
+        if progress.debugger.as_ref().is_some_and(|debugger| debugger.has_quit()) {
+            break;
+        }
+        number += 1;
+        progress.lines += 1;
+        let position = cmd::Position {
+            file: file.to_owned(),
+            line: progress.lines,
+            file_line: number,
+        };
+        let record = record.map_err(|error| StreamError {
+            line: number,
+            error,
+        })?;
+        let pass = passed(&record);
+        let line = record.text;
+        let mut ending = record.ending;
+        let mut raw = record.raw;
+
+        // Lines appended by `join_next` are read here. A read error ends
+        // the input for the program and is reported once it is done, and
+        // so does a record that is passed through.
+        let mut joined = 0;
+        let mut read_error = None;
+        let mut input = || match lines.next_if(|next| !matches!(next, Ok(next) if passed(next)))? {
+            Ok(record) => {
+                joined += 1;
+                ending = record.ending;
+                raw |= record.raw;
+                Some(record.text)
+            }
+            Err(error) => {
+                read_error = Some(error);
+                None
+            }
+        };
+
+        let mut line_store = None;
+        let store = if options.persist {
+            &mut progress.store
+        } else {
+            line_store.get_or_insert_with(|| progress.store.clone())
+        };
+
+        let edited = if pass || !progress.selection.selects(position.line, &line) {
+            Ok(vec![line])
+        } else if let Some(debugger) = progress.debugger.as_mut() {
+            debugger.edit(&line, position, &mut input, store, bytes)
+        } else if options.trace {
+            let mut trace = stderr.lock();
+            writeln!(trace, "line {}:", number).and_then(|_| {
+                cmd::run_traced(program, &line, position, &mut input, store, bytes, &mut trace)
+            })
+        } else {
+            Ok(cmd::run_on_input(program, &line, position, &mut input, store, bytes))
+        };
+        number += joined;
+        progress.lines += joined;
+        // The hold space lives for the whole input, persisting or not.
+        if let Some(line_store) = line_store {
+            progress.store.hold = line_store.hold;
+        }
+
+        let at_line = |error| StreamError {
+            line: number,
+            error,
+        };
+        if let Some(error) = read_error {
+            return Err(StreamError {
+                line: number + 1,
+                error,
+            });
+        }
+        let edited = edited.map_err(at_line)?;
+        for (i, line) in edited.iter().enumerate() {
+            let ending = line_ending(options, &ending, i + 1 == edited.len());
+            write_line(&mut output, line, ending, raw, options.output_encoding).map_err(at_line)?;
+        }
+        if options.flush == Flush::Line {
+            output.flush().map_err(at_line)?;
+        }
+    }
+
+    output.flush().map_err(|error| StreamError {
+        line: number,
+        error,
+    })
+}
+
+/// Expands the glob patterns among `patterns`, in order. Other names are kept
+/// as they are.
+fn expand_globs(patterns: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            files.push(pattern.clone());
+            continue;
+        }
+        let before = files.len();
+        for path in glob::glob(pattern)? {
+            files.push(path?.to_string_lossy().into_owned());
+        }
+        if files.len() == before {
+            return Err(format!("No files match {}.", pattern).into());
+        }
+    }
+    Ok(files)
+}
+
+/// A program with its directives and `at_begin` and `at_end` blocks. This
+/// is what `--emit-ast` prints and `--program-json` loads.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Script {
+    cmds: Vec<Cmd>,
+    #[serde(default, rename = "lines")]
+    address: Option<Address>,
+    #[serde(default, rename = "at_begin")]
+    begin: Vec<Cmd>,
+    #[serde(default, rename = "at_end")]
+    end: Vec<Cmd>,
+    #[serde(default)]
+    persist: bool,
+}
+
+/// Loads a program together with its directives. Only the lined dialect and
+/// JSON programs have directives.
+fn load_script(loc: &ProgramLocation, dialect: Dialect) -> Result<Script, Box<dyn Error>> {
+    Ok(load_located_script(loc, dialect)?.0)
+}
+
+/// Same as `load_script`, also returning the program line of every top level
+/// command. Commands of JSON and vim programs are numbered instead.
+fn load_located_script(
+    loc: &ProgramLocation,
+    dialect: Dialect,
+) -> Result<(Script, Vec<usize>), Box<dyn Error>> {
+    let text = match (loc, dialect) {
+        (ProgramLocation::Text(text), Dialect::Lined) => text.to_string(),
+        (ProgramLocation::File(path), Dialect::Lined) => slurp(path)?,
+        _ => {
+            let script = match loc {
+                ProgramLocation::JsonFile(path) => serde_json::from_str(&slurp(path)?)?,
+                _ => Script {
+                    cmds: load_program(loc, dialect)?,
+                    address: None,
+                    begin: Vec::new(),
+                    end: Vec::new(),
+                    persist: false,
+                },
+            };
+            let lines = (1..=script.cmds.len()).collect();
+            return Ok((script, lines));
+        }
+    };
+    let program = parser::parse_program(&mut text.chars().tokens())?;
+    if program.cmds.is_empty() && program.begin.is_empty() && program.end.is_empty() {
+        return Err("Empty program.".into());
+    }
+    let (lines, cmds) = program.cmds.into_iter().unzip();
+    let script = Script {
+        cmds,
+        address: program.address,
+        begin: program.begin,
+        end: program.end,
+        persist: program.persist,
+    };
+    Ok((script, lines))
+}
+
+/// The records an `at_begin` or `at_end` block writes.
+fn block_lines(block: &[Cmd], progress: &mut Progress, options: &Options) -> Vec<String> {
+    if block.is_empty() {
+        return Vec::new();
+    }
+    let position = cmd::Position {
+        file: "-".to_owned(),
+        line: progress.lines,
+        file_line: 0,
+    };
+    let raw = options.decoding == Decoding::Bytes;
+    cmd::run_block(block, position, &mut progress.store, raw)
+}
+
+/// Runs an `at_begin` or `at_end` block, writing its records to `output`.
+fn run_block<W: Write>(
+    block: &[Cmd],
+    progress: &mut Progress,
+    options: &Options,
+    output: &mut W,
+) -> io::Result<()> {
+    if block.is_empty() {
+        return Ok(());
+    }
+    let raw = options.decoding == Decoding::Bytes;
+    for line in block_lines(block, progress, options) {
+        write_line(
+            output,
+            &line,
+            options.output_separator(),
+            raw,
+            options.output_encoding,
+        )?;
+    }
+    output.flush()
+}
+
+/// Same as `edit_stream` for a file written on its own, which the `at_begin`
+/// and `at_end` blocks of `script` write to as well.
+fn edit_script<R: BufRead, W: Write>(
+    script: &Script,
+    input: R,
+    mut output: W,
+    options: &Options,
+    file: &str,
+    progress: &mut Progress,
+) -> Result<(), Box<dyn Error>> {
+    run_block(&script.begin, progress, options, &mut output)?;
+    edit_stream(&script.cmds, input, &mut output, options, file, progress)?;
+    run_block(&script.end, progress, options, &mut output)?;
+    Ok(())
+}
+
+pub fn run(cfg: &Cfg) -> Result<(), Box<dyn Error>> {
+    let script = load_script(&cfg.program, cfg.dialect)?;
+    // An address given in the configuration overrides the program's.
+    let mut options = cfg.options.clone();
+    if options.address.is_none() {
+        options.address = script.address.clone();
+    }
+    options.persist |= script.persist;
+
+    if cfg.emit_ast {
+        println!("{}", serde_json::to_string_pretty(&script)?);
+        return Ok(());
+    }
+
+    let files = expand_globs(&cfg.files)?;
+    let mut progress = Progress::new(&options);
+    edit_inputs(&script, &files, cfg, &options, &mut progress)
+}
+
+/// Edits the inputs. Editing in place, diffing and checking write every file
+/// on its own, with the `at_begin` and `at_end` blocks run for each of them.
+fn edit_inputs(
+    script: &Script,
+    files: &[String],
+    cfg: &Cfg,
+    options: &Options,
+    progress: &mut Progress,
+) -> Result<(), Box<dyn Error>> {
+    if let Output::Diff { .. } | Output::Check = cfg.output {
+        return check_changes(script, files, cfg, options, progress);
+    }
+
+    if let Output::InPlace { backup_suffix } = &cfg.output {
+        if files.is_empty() {
+            return Err("Editing in place needs files to edit.".into());
+        }
+        let backup_suffix = backup_suffix.as_deref();
+        for path in files {
+            files::edit_in_place(script, path, progress, backup_suffix, options)
+                .map_err(|err| format!("{}: {}", path, err))?;
+        }
+        return Ok(());
+    }
+
+    let program = &script.cmds;
+    let stdout = io::stdout();
+    run_block(&script.begin, progress, options, &mut stdout.lock())?;
+    if files.is_empty() {
+        let stdin = io::stdin();
+        edit_stream(program, stdin.lock(), stdout.lock(), options, "-", progress)?;
+    }
+    for path in files {
+        let input = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+        let input = io::BufReader::new(input);
+        edit_stream(program, input, stdout.lock(), options, path, progress)
+            .map_err(|err| format!("{}: {}", path, err))?;
+    }
+    run_block(&script.end, progress, options, &mut stdout.lock())?;
+
+    Ok(())
+}
+
+/// Edits every input in memory and reports the changes as `cfg.output` asks.
+fn check_changes(
+    script: &Script,
+    files: &[String],
+    cfg: &Cfg,
+    options: &Options,
+    progress: &mut Progress,
+) -> Result<(), Box<dyn Error>> {
+    let stdin_only = ["-".to_owned()];
+    let inputs = if files.is_empty() { &stdin_only[..] } else { files };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut changed = 0;
+
+    for name in inputs {
+        let mut original = Vec::new();
+        if files.is_empty() {
+            io::stdin().read_to_end(&mut original)?;
+        } else {
+            original = std::fs::read(name).map_err(|err| format!("{}: {}", name, err))?;
+        }
+        let mut edited = Vec::new();
+        edit_script(script, &original[..], &mut edited, options, name, progress)
+            .map_err(|err| format!("{}: {}", name, err))?;
+
+        if original == edited {
+            continue;
+        }
+        changed += 1;
+        let original = encoding::decode(&original, options.input_encoding);
+        let edited = encoding::decode(&edited, options.output_encoding);
+        let old: Vec<&str> = original.split_terminator('\n').collect();
+        let new: Vec<&str> = edited.split_terminator('\n').collect();
+        match cfg.output {
+            Output::Check => writeln!(out, "{}", name)?,
+            _ if old == new => writeln!(out, "{}: only the final newline or byte order mark changes", name)?,
+            _ => write!(out, "{}", diff::unified(name, &old, &new))?,
+        }
+    }
+
+    match cfg.output {
+        Output::Check | Output::Diff { check: true } if changed > 0 => {
+            Err(format!("{} of {} inputs would change.", changed, inputs.len()).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Steps through the program for every line on stdin, reading debugger
+/// commands from the terminal.
+pub fn debug(
+    location: &ProgramLocation,
+    dialect: Dialect,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let (script, lines) = load_located_script(location, dialect)?;
+    let terminal = File::open("/dev/tty")
+        .map_err(|err| format!("Can not read debugger commands from the terminal: {}", err))?;
+    let mut terminal = io::BufReader::new(terminal);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    debug_stream(&script, &lines, stdin.lock(), &mut terminal, &mut stdout.lock(), options)
+}
+
+/// Steps through `script` for every record of `input` as `edit_inputs` runs
+/// it, showing the edited records instead of writing them.
+fn debug_stream<R: BufRead>(
+    script: &Script,
+    lines: &[usize],
+    input: R,
+    commands: &mut dyn BufRead,
+    out: &mut dyn Write,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let mut options = options.clone();
+    if options.address.is_none() {
+        options.address = script.address.clone();
+    }
+    options.persist |= script.persist;
+
+    let mut debugger = debugger::Debugger::new(&script.cmds, lines, commands, out);
+    let mut progress = Progress::new(&options);
+    let begin = block_lines(&script.begin, &mut progress, &options);
+    if !script.begin.is_empty() {
+        debugger.show_block("at_begin", &begin)?;
+    }
+    progress.debugger = Some(&mut debugger);
+    edit_stream(&script.cmds, input, io::sink(), &options, "-", &mut progress)?;
+    let debugger = progress.debugger.take().unwrap();
+    if !script.end.is_empty() && !debugger.has_quit() {
+        let end = block_lines(&script.end, &mut progress, &options);
+        debugger.show_block("at_end", &end)?;
+    }
+    Ok(())
+}
+
+/// Describes every step of the program in plain English, showing the line
+/// after each step if a `sample` line is given.
+pub fn explain(
+    location: &ProgramLocation,
+    dialect: Dialect,
+    sample: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let script = load_script(location, dialect)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    explain::explain_block("at_begin", &script.begin, &mut out)?;
+    explain::explain(&script.cmds, sample, &mut out)?;
+    explain::explain_block("at_end", &script.end, &mut out)?;
+    Ok(())
+}
+
+/// Runs the inline test cases of the program file at `path`, failing if
+/// there are none or if any of them fails.
+pub fn test(path: &str) -> Result<(), Box<dyn Error>> {
+    let program = parser::parse_program(&mut slurp(path)?.chars().tokens())?;
+    if program.tests.is_empty() {
+        return Err(format!("No tests in {}.", path).into());
+    }
+    let stdout = io::stdout();
+    let failed = testing::run_tests(&program, &mut stdout.lock())?;
+    if failed > 0 {
+        return Err(format!("{} of {} tests failed.", failed, program.tests.len()).into());
+    }
+    Ok(())
+}
+
+/// Edits `sample` interactively with commands read from stdin. The entered
+/// commands are saved as a program file to `save`, if given.
+pub fn repl(sample: &str, save: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut session = repl::Session::new(sample);
 
     let stdin = io::stdin();
-    for line in stdin.lock().lines() {
-        println!("{}", cmd::run(&program, &line.unwrap()));
+    let stdout = io::stdout();
+    session.run(&mut stdin.lock(), &mut stdout.lock())?;
+
+    if let Some(path) = save {
+        std::fs::write(path, session.program() + "\n")?;
     }
+    Ok(())
+}
+
+/// Records a program by editing `sample` with emacs keys in the terminal. The
+/// program is written to `save`, if given, otherwise to stdout.
+pub fn record(sample: &str, save: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let program = recorder::record(sample)?;
+    write_program(&program, save)
+}
 
+/// Translates an emacs keyboard macro in `edmacro` notation into a program,
+/// written to `save` if given, otherwise to stdout.
+pub fn import_emacs(path: &str, save: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let program = emacs::import(&slurp(path)?)?;
+    write_program(&parser::unparse(&program), save)
+}
+
+/// Translates a sed script of `s` and `y` commands into a program, written
+/// to `save` if given, otherwise to stdout.
+pub fn from_sed(script: &str, save: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let program = sed::translate_script(script)?;
+    write_program(&parser::unparse(&program), save)
+}
+
+fn write_program(program: &str, save: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match save {
+        Some(path) => std::fs::write(path, program)?,
+        None => print!("{}", program),
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn edit(program: &str, input: &[u8]) -> Result<String, StreamError> {
+        let program = compile(program).unwrap();
+        let mut output = Vec::new();
+        run_stream(&program, input, &mut output, &Options::default())?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn run_stream_edits_every_line() {
+        let output = edit("trim_line upcase_line", b"  one\ntwo  \n").unwrap();
+        assert_eq!("ONE\nTWO\n", output);
+    }
+
+    #[test]
+    fn run_stream_stops_editing_when_done() {
+        assert_eq!("a b\n", edit("nextline upcase_line", b"a b\n").unwrap());
+        assert_eq!("A1\nb\n", edit(r#"find "=" delete upcase_line"#, b"a=1\nb\n").unwrap());
+        let program = r#"repeat 9 { find_next "a" delete } upcase_line"#;
+        assert_eq!("bnn\n", edit(program, b"banana\n").unwrap());
+        let program = r#"{ rfind "x" insert "-" } insert "+""#;
+        assert_eq!("ab\n", edit(program, b"ab\n").unwrap());
+    }
+
+    #[test]
+    fn run_stream_edits_addressed_lines() {
+        let program = compile("upcase_line").unwrap();
+        let options = Options {
+            address: Some("/b/,3".parse().unwrap()),
+            ..Options::default()
+        };
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\nb\nc\nd\n"[..], &mut output, &options).unwrap();
+        assert_eq!("a\nB\nC\nd\n", String::from_utf8(output).unwrap());
+
+        let text = r#"lines "2" upcase_line"#.to_owned();
+        let script = load_script(&ProgramLocation::Text(text), Dialect::Lined).unwrap();
+        assert_eq!(Some(Address::Single(address::Selector::Line(2))), script.address);
+    }
+
+    #[test]
+    fn run_stream_writes_every_output_line() {
+        let output = edit(r#"if_line 2 delete_line emit "--" print_clipboard"#, b"a\nb\n");
+        assert_eq!("--\na\n--\n", output.unwrap());
+    }
+
+    #[test]
+    fn run_stream_splits_and_joins_lines() {
+        let output = edit(r#"join_next ";" find ";" split_line delete"#, b"a\nb\n");
+        assert_eq!("a\nb\n", output.unwrap());
+        let output = edit(r#"join_next "-" insert_template "{line}:""#, b"a\nb\nc\n");
+        assert_eq!("1:a-b\n3:c\n", output.unwrap());
+
+        let err = edit(r#"join_next "-""#, b"one\n\xff\n").unwrap_err();
+        assert_eq!(2, err.line);
+    }
+
+    #[test]
+    fn run_stream_persists_store() {
+        let program = compile(r#"increment "n" insert_counter "n" insert " ""#).unwrap();
+        let mut options = Options::default();
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\nb\n"[..], &mut output, &options).unwrap();
+        assert_eq!("1 a\n1 b\n", String::from_utf8(output).unwrap());
+
+        options.persist = true;
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\nb\n"[..], &mut output, &options).unwrap();
+        assert_eq!("1 a\n2 b\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn run_stream_keeps_hold_space() {
+        let output = edit("get_append hold", b"a\nb\nc\n");
+        assert_eq!("a\n\nb\na\n\nc\nb\na\n\n", output.unwrap());
+        let output = edit("get_append", b"a\nb\n");
+        assert_eq!("a\n\nb\n\n", output.unwrap());
+    }
+
+    #[test]
+    fn run_stream_with_record_separators() {
+        let program = compile("upcase_line").unwrap();
+        let mut options = Options {
+            separator: Separator::String("\0".to_owned()),
+            ..Options::default()
+        };
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\nb\0c"[..], &mut output, &options).unwrap();
+        assert_eq!("A\nB\0C", String::from_utf8(output).unwrap());
+
+        options.separator = Separator::Paragraph;
+        options.output_separator = Some("|".to_owned());
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\nb\n\nc\n"[..], &mut output, &options).unwrap();
+        assert_eq!("A\nB|C|", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn run_stream_keeps_line_endings() {
+        let output = edit("upcase_line", b"\xef\xbb\xbfa\r\nb\nc").unwrap();
+        assert_eq!("\u{feff}A\r\nB\nC", output);
+        let output = edit(r#"emit_after "-""#, b"a\r\nb").unwrap();
+        assert_eq!("a\r\n-\r\nb\n-", output);
+
+        let program = compile("upcase_line").unwrap();
+        let options = Options {
+            output_separator: Some("\n".to_owned()),
+            strip_bom: true,
+            ..Options::default()
+        };
+        let mut output = Vec::new();
+        run_stream(&program, &b"\xef\xbb\xbfa\r\nb"[..], &mut output, &options).unwrap();
+        assert_eq!("A\nB\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn run_stream_with_invalid_utf8() {
+        let program = compile("upcase_line forward delete").unwrap();
+        let input = &b"ab\n\xe9t\xc3\xa9\n"[..];
+        let mut options = Options::default();
+        let mut output = Vec::new();
+        let err = run_stream(&program, input, &mut output, &options).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.error.kind());
+        assert_eq!(2, err.line);
+
+        let mut edit = |decoding| {
+            options.decoding = decoding;
+            let mut output = Vec::new();
+            run_stream(&program, input, &mut output, &options).unwrap();
+            output
+        };
+        assert_eq!(&b"A\n\xe9t\xc3\xa9\n"[..], &edit(Decoding::PassThrough)[..]);
+        assert_eq!(&b"A\n\xe9\xc3\xa9\n"[..], &edit(Decoding::Bytes)[..]);
+        assert_eq!("A\n\u{fffd}\u{c9}\n", String::from_utf8(edit(Decoding::Lossy)).unwrap());
+
+        // Only ASCII changes case byte by byte, inserted text included.
+        options.decoding = Decoding::Bytes;
+        let program = compile(r#"insert "é" upcase_line"#).unwrap();
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\xff\n"[..], &mut output, &options).unwrap();
+        assert_eq!(&b"\xc3\xa9A\xff\n"[..], &output[..]);
+
+        // Records passed through are not joined to the line before them.
+        options.decoding = Decoding::PassThrough;
+        let program = compile(r#"join_next "+" upcase_line"#).unwrap();
+        let mut output = Vec::new();
+        run_stream(&program, &b"a\n\xff\nb\nc\n"[..], &mut output, &options).unwrap();
+        assert_eq!(&b"A\n\xff\nB+C\n"[..], &output[..]);
+    }
+
+    #[test]
+    fn run_stream_with_encodings() {
+        let program = compile("upcase_line").unwrap();
+        let options = Options {
+            input_encoding: Some("utf-16le".parse().unwrap()),
+            output_encoding: Some("windows-1252".parse().unwrap()),
+            ..Options::default()
+        };
+        let mut output = Vec::new();
+        let input = &b"\xff\xfe\xe9\x00\r\x00\n\x00"[..];
+        run_stream(&program, input, &mut output, &options).unwrap();
+        assert_eq!(&b"\xc9\r\n"[..], &output[..]);
+
+        let program = compile(r#"insert "α""#).unwrap();
+        let err = run_stream(&program, &b"a\x00"[..], &mut output, &options).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.error.kind());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let program = compile(r#"repeat 2 forward insert "x" truncate_by 1"#).unwrap();
+        let json = to_json(&program).unwrap();
+        assert_eq!(program, from_json(&json).unwrap());
+
+        let mut table = HashMap::new();
+        table.insert('a', 'b');
+        let program = vec![Cmd::Translate { table }, Cmd::Goto(3)];
+        assert_eq!(program, from_json(&to_json(&program).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn script_json_round_trip() {
+        let text = r#"lines "/a.b/,3" persist at_begin { insert "x" } upcase_line at_end { print }"#;
+        let script = load_script(&ProgramLocation::Text(text.to_owned()), Dialect::Lined);
+        let script = script.unwrap();
+        let json = serde_json::to_string_pretty(&script).unwrap();
+        assert!(json.contains(r#""lines": "/a.b/,3""#), "{}", json);
+        assert_eq!(script, serde_json::from_str(&json).unwrap());
+
+        let script: Script = serde_json::from_str(r#"{"cmds": ["upcase_line"]}"#).unwrap();
+        assert_eq!(vec![Cmd::UpcaseLine], script.cmds);
+        assert!(script.address.is_none() && script.begin.is_empty() && !script.persist);
+    }
+
+    #[test]
+    fn run_stream_reports_line_number() {
+        let err = edit("upcase_line", b"one\ntwo\n\xff\n").unwrap_err();
+        assert_eq!(3, err.line);
+        assert_eq!(io::ErrorKind::InvalidData, err.error.kind());
+    }
+
+    #[test]
+    fn expand_glob_patterns() {
+        let files = expand_globs(&["src/cmds/k*.rs".to_owned(), "missing.txt".to_owned()]);
+        assert_eq!(vec!["src/cmds/kill.rs", "missing.txt"], files.unwrap());
+        assert!(expand_globs(&["src/*.missing".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn compile_vim_dialect() {
+        let vim = compile_as("0 f, gUiw", Dialect::Vim).unwrap();
+        assert_eq!(compile("home find_next \",\" { select_word upcase }").unwrap(), vim);
+        assert!(compile_as("", Dialect::Vim).is_err());
+    }
+}
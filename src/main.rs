@@ -1,60 +1,520 @@
 extern crate clap;
 extern crate lined;
 
-use clap::{App, Arg, ArgGroup};
-use lined::{Cfg, ProgramLocation};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use lined::{Address, Cfg, Charset, Decoding, Dialect, Flush, Options, Output, ProgramLocation, Separator};
+use std::env;
 use std::error::Error;
 
-fn config() -> Result<Cfg, Box<Error>> {
-    let args = App::new("lined")
+fn program_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("program")
+            .short("p")
+            .long("programtext")
+            .value_name("text")
+            .help("The line editing program to run.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("file")
+            .short("f")
+            .long("programfile")
+            .value_name("file")
+            .help("A file containing the line editing program to run..")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("json")
+            .long("program-json")
+            .value_name("file")
+            .help("A file containing a program serialized as JSON, see --emit-ast.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("dialect")
+            .long("dialect")
+            .value_name("syntax")
+            .help("The syntax the program text is written in.")
+            .possible_values(&["lined", "vim"])
+            .default_value("lined"),
+    )
+    .group(
+        ArgGroup::with_name("prg")
+            .args(&["program", "file", "json"])
+            .required(true),
+    )
+}
+
+/// The options that control how records are read and selected.
+fn stream_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("lines")
+            .long("lines")
+            .value_name("address")
+            .help("Only edit the selected lines: N, N,M, /re/, /a/,/b/ or first~step. Other lines pass through.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("persist")
+            .long("persist")
+            .help("Carry the clipboard, registers and counters over from line to line."),
+    )
+    .arg(
+        Arg::with_name("null-data")
+            .short("z")
+            .long("null-data")
+            .help("Records end with NUL instead of newline, as from find -print0."),
+    )
+    .arg(
+        Arg::with_name("rs")
+            .long("rs")
+            .value_name("separator")
+            .help("Records end with the separator, which may contain \\n, \\t and \\0.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("rs-regex")
+            .long("rs-regex")
+            .value_name("regex")
+            .help("Records are separated by matches of the regular expression.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("paragraph")
+            .long("paragraph")
+            .help("Records are paragraphs separated by blank lines."),
+    )
+    .arg(
+        Arg::with_name("whole")
+            .long("whole")
+            .help("Edit the whole input as one record with embedded newlines."),
+    )
+    .group(ArgGroup::with_name("separator").args(&[
+        "null-data",
+        "rs",
+        "rs-regex",
+        "paragraph",
+        "whole",
+    ]))
+    .arg(
+        Arg::with_name("bytes")
+            .long("bytes")
+            .help("Edit records byte by byte instead of as UTF-8. Case changes only affect ASCII."),
+    )
+    .arg(
+        Arg::with_name("lossy")
+            .long("lossy")
+            .help("Replace input that cannot be decoded with U+FFFD instead of failing."),
+    )
+    .arg(
+        Arg::with_name("pass-invalid")
+            .long("pass-invalid")
+            .help("Pass records that are not valid UTF-8 through unedited instead of failing."),
+    )
+    .group(ArgGroup::with_name("decoding").args(&["bytes", "lossy", "pass-invalid"]))
+    .arg(
+        Arg::with_name("input-encoding")
+            .long("input-encoding")
+            .value_name("encoding")
+            .help("Read the input in the encoding, such as windows-1252 or utf-16le, instead of UTF-8.")
+            .takes_value(true)
+            .conflicts_with_all(&["bytes", "pass-invalid"]),
+    )
+}
+
+fn program_location(args: &ArgMatches) -> ProgramLocation {
+    if let Some(text) = args.value_of("program") {
+        ProgramLocation::Text(text.to_string())
+    } else if let Some(path) = args.value_of("json") {
+        ProgramLocation::JsonFile(path.to_string())
+    } else {
+        ProgramLocation::File(args.value_of("file").unwrap().to_string())
+    }
+}
+
+fn dialect(args: &ArgMatches) -> Dialect {
+    match args.value_of("dialect") {
+        Some("vim") => Dialect::Vim,
+        _ => Dialect::Lined,
+    }
+}
+
+/// Replaces the escapes `\n`, `\t`, `\0` and `\\` in a separator given on
+/// the command line.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(escaped @ ('n' | 't' | '0' | '\\'))) => {
+                chars.next();
+                unescaped.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    '0' => '\0',
+                    _ => '\\',
+                });
+            }
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn separator(args: &ArgMatches) -> Result<Separator, Box<dyn Error>> {
+    let separator = if args.is_present("null-data") {
+        Separator::String("\0".to_owned())
+    } else if let Some(separator) = args.value_of("rs") {
+        if separator.is_empty() {
+            return Err("The record separator must not be empty.".into());
+        }
+        Separator::String(unescape(separator))
+    } else if let Some(pattern) = args.value_of("rs-regex") {
+        Separator::regex(pattern)?
+    } else if args.is_present("paragraph") {
+        Separator::Paragraph
+    } else if args.is_present("whole") {
+        Separator::Whole
+    } else {
+        Separator::Newline
+    };
+    Ok(separator)
+}
+
+fn options(args: &ArgMatches) -> Result<Options, Box<dyn Error>> {
+    let flush = if args.is_present("line-buffered") {
+        Flush::Line
+    } else {
+        Flush::Buffered
+    };
+
+    Ok(Options {
+        flush,
+        trace: args.is_present("trace"),
+        address: match args.value_of("lines") {
+            Some(address) => Some(address.parse::<Address>()?),
+            None => None,
+        },
+        persist: args.is_present("persist"),
+        separator: separator(args)?,
+        output_separator: match args.value_of("eol") {
+            Some("crlf") => Some("\r\n".to_owned()),
+            Some(_) => Some("\n".to_owned()),
+            None => args.value_of("ors").map(unescape),
+        },
+        strip_bom: args.is_present("strip-bom"),
+        decoding: if args.is_present("bytes") {
+            Decoding::Bytes
+        } else if args.is_present("lossy") {
+            Decoding::Lossy
+        } else if args.is_present("pass-invalid") {
+            Decoding::PassThrough
+        } else {
+            Decoding::Utf8
+        },
+        input_encoding: match args.value_of("input-encoding") {
+            Some(label) => Some(label.parse::<Charset>()?),
+            None => None,
+        },
+        output_encoding: match args.value_of("output-encoding") {
+            Some(label) => Some(label.parse::<Charset>()?),
+            None => None,
+        },
+    })
+}
+
+fn config(args: &ArgMatches) -> Result<Cfg, Box<dyn Error>> {
+    let output = if args.is_present("in-place") {
+        Output::InPlace {
+            backup_suffix: args.value_of("in-place").map(|suffix| suffix.to_string()),
+        }
+    } else if args.is_present("diff") {
+        Output::Diff {
+            check: args.is_present("check"),
+        }
+    } else if args.is_present("check") {
+        Output::Check
+    } else {
+        Output::Stdout
+    };
+
+    Ok(Cfg {
+        program: program_location(args),
+        files: args
+            .values_of("files")
+            .map_or(Vec::new(), |files| files.map(|file| file.to_string()).collect()),
+        output,
+        dialect: dialect(args),
+        options: options(args)?,
+        emit_ast: args.is_present("emit-ast"),
+    })
+}
+
+/// Options that take their value from the next argument.
+const VALUE_OPTIONS: &[&str] = &[
+    "-p",
+    "--programtext",
+    "-f",
+    "--programfile",
+    "--program-json",
+    "--dialect",
+    "--lines",
+    "--rs",
+    "--rs-regex",
+    "--ors",
+    "--eol",
+    "--input-encoding",
+    "--output-encoding",
+];
+
+/// clap does not take an optional value attached to a short option, so the
+/// sed style `-i.bak` is rewritten to `--in-place=.bak`. Only the options
+/// before the first file or subcommand are rewritten, and none after `--`.
+fn in_place_args<I: IntoIterator<Item = String>>(args: I) -> Vec<String> {
+    let mut args = args.into_iter();
+    let mut rewritten: Vec<String> = args.next().into_iter().collect();
+    let mut value = false;
+    for arg in args.by_ref() {
+        if value {
+            value = false;
+        } else if arg == "--" || !arg.starts_with('-') || arg == "-" {
+            rewritten.push(arg);
+            break;
+        } else if arg.starts_with("-i") && arg.len() > 2 && !arg.starts_with("-i=") {
+            rewritten.push(format!("--in-place={}", &arg[2..]));
+            continue;
+        } else {
+            value = VALUE_OPTIONS.contains(&arg.as_str());
+        }
+        rewritten.push(arg);
+    }
+    rewritten.extend(args);
+    rewritten
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let app = App::new("lined")
         .version("0.1")
         .author("Tord Svensson <tord.svensson@gmail.com>")
         .about("A simple non-interactive line editor.")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands);
+
+    let args = stream_args(program_args(app))
         .arg(
-            Arg::with_name("program")
-                .short("p")
-                .long("programtext")
-                .value_name("text")
-                .help("The line editing program to run.")
-                .takes_value(true),
+            Arg::with_name("emit-ast")
+                .long("emit-ast")
+                .help("Print the parsed program with its directives and blocks as JSON and exit."),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .help("Print every executed command and the resulting line to stderr."),
+        )
+        .arg(
+            Arg::with_name("line-buffered")
+                .long("line-buffered")
+                .help("Flush the output after every line."),
         )
         .arg(
-            Arg::with_name("file")
-                .short("f")
-                .long("programfile")
-                .value_name("file")
-                .help("A file containing the line editing program to run..")
+            Arg::with_name("ors")
+                .long("ors")
+                .value_name("separator")
+                .help("Write the separator after every record instead of the input separator.")
                 .takes_value(true),
         )
-        .group(
-            ArgGroup::with_name("prg")
-                .args(&["program", "file"])
-                .required(true),
+        .arg(
+            Arg::with_name("eol")
+                .long("eol")
+                .value_name("ending")
+                .help("End every record with the line ending instead of its own.")
+                .possible_values(&["lf", "crlf"])
+                .conflicts_with("ors"),
         )
-        .get_matches();
-
-    let program = if let Some(text) = args.value_of("program") {
-        ProgramLocation::Text(text.to_string())
-    } else {
-        ProgramLocation::File(args.value_of("file").unwrap().to_string())
-    };
-
-    Ok(Cfg { program })
-}
-
-fn run() -> Result<(), Box<Error>> {
-    let cfg = config()?;
-    lined::run(&cfg)?;
+        .arg(
+            Arg::with_name("strip-bom")
+                .long("strip-bom")
+                .help("Leave a byte order mark at the start of the input out of the output."),
+        )
+        .arg(
+            Arg::with_name("output-encoding")
+                .long("output-encoding")
+                .value_name("encoding")
+                .help("Write the output in the encoding instead of UTF-8.")
+                .takes_value(true)
+                .conflicts_with_all(&["bytes", "pass-invalid"]),
+        )
+        .arg(
+            Arg::with_name("in-place")
+                .short("i")
+                .long("in-place")
+                .value_name("suffix")
+                .help("Edit the files in place, keeping backups with the suffix if given (-i.bak).")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("diff")
+                .long("diff")
+                .help("Print a unified diff of the changes instead of the edited lines.")
+                .conflicts_with("in-place"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Fail if the program would change any input, printing the names of those inputs.")
+                .conflicts_with("in-place"),
+        )
+        .arg(
+            Arg::with_name("files")
+                .help("The files to edit. Lines are read from stdin if none are given.")
+                .multiple(true),
+        )
+        .subcommand(stream_args(program_args(
+            SubCommand::with_name("debug")
+                .about("Steps through the program one command at a time for every line on stdin."),
+        )))
+        .subcommand(
+            program_args(
+                SubCommand::with_name("explain")
+                    .about("Describes the program step by step in plain English."),
+            )
+            .arg(
+                Arg::with_name("sample")
+                    .help("A line to show the effect of every step on."),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Runs the `test \"input\" => \"output\"` cases of a program file.")
+                .arg(
+                    Arg::with_name("programfile")
+                        .help("The program file with the tests.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Edits a sample line interactively, one command at a time.")
+                .arg(
+                    Arg::with_name("sample")
+                        .help("The line to edit.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("save")
+                        .short("o")
+                        .long("save")
+                        .value_name("file")
+                        .help("Save the entered commands as a program file when done.")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("record")
+                .about("Records a program by editing a sample line with emacs keys.")
+                .arg(
+                    Arg::with_name("sample")
+                        .help("The line to edit.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("save")
+                        .short("o")
+                        .long("save")
+                        .value_name("file")
+                        .help("Save the recorded program to a file instead of printing it.")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import-emacs")
+                .about("Translates an emacs keyboard macro in edmacro notation into a program.")
+                .arg(
+                    Arg::with_name("macro")
+                        .help("A file with the macro, as shown by edit-kbd-macro.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("save")
+                        .short("o")
+                        .long("save")
+                        .value_name("file")
+                        .help("Save the program to a file instead of printing it.")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("from-sed")
+                .about("Translates a sed script of s and y commands into a program.")
+                .arg(
+                    Arg::with_name("script")
+                        .help("The sed script, for example 's/foo/bar/g; y/abc/xyz/'.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("save")
+                        .short("o")
+                        .long("save")
+                        .value_name("file")
+                        .help("Save the program to a file instead of printing it.")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches_from(in_place_args(env::args()));
 
-    Ok(())
+    match args.subcommand() {
+        ("debug", Some(args)) => {
+            lined::debug(&program_location(args), dialect(args), &options(args)?)
+        }
+        ("explain", Some(args)) => lined::explain(
+            &program_location(args),
+            dialect(args),
+            args.value_of("sample"),
+        ),
+        ("test", Some(args)) => lined::test(args.value_of("programfile").unwrap()),
+        ("record", Some(args)) => {
+            lined::record(args.value_of("sample").unwrap(), args.value_of("save"))
+        }
+        ("import-emacs", Some(args)) => {
+            lined::import_emacs(args.value_of("macro").unwrap(), args.value_of("save"))
+        }
+        ("from-sed", Some(args)) => {
+            lined::from_sed(args.value_of("script").unwrap(), args.value_of("save"))
+        }
+        ("repl", Some(args)) => lined::repl(args.value_of("sample").unwrap(), args.value_of("save")),
+        _ => lined::run(&config(&args)?),
+    }
 }
 
 fn main() {
     ::std::process::exit(match run() {
         Ok(_) => 0,
         Err(err) => {
-            eprintln!("Error {}", err.description());
+            eprintln!("Error {}", err);
             1
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(args: &str) -> String {
+        in_place_args(args.split(' ').map(|arg| arg.to_owned())).join(" ")
+    }
+
+    #[test]
+    fn attached_in_place_suffix() {
+        assert_eq!(
+            "lined -p upcase_line --in-place=.bak a -i.x",
+            rewritten("lined -p upcase_line -i.bak a -i.x")
+        );
+        assert_eq!("lined -i --rs -i.bak", rewritten("lined -i --rs -i.bak"));
+        assert_eq!("lined -i=.bak -- -i.bak", rewritten("lined -i=.bak -- -i.bak"));
+        assert_eq!("lined test -i.bak", rewritten("lined test -i.bak"));
+    }
+}
@@ -1,4 +1,8 @@
-use cmd::Cmd;
+use address::{Address, AddressError};
+use cmd::{Cmd, Condition};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use tokenizer::Token;
 use tokenizer::Tokenizer;
 
@@ -47,129 +51,677 @@ const KW_DOWNCASE_LINE: &str = "downcase_line";
 const KW_LTRIM_LINE: &str = "ltrim_line";
 const KW_RTRIM_LINE: &str = "rtrim_line";
 const KW_TRIM_LINE: &str = "trim_line";
+const KW_GOTO: &str = "goto";
+const KW_FIND: &str = "find";
+const KW_RFIND: &str = "rfind";
+const KW_TRANSLATE: &str = "translate";
+const KW_SUBSTITUTE: &str = "substitute";
+const KW_SUBSTITUTE_ALL: &str = "substitute_all";
+const KW_INSERT_TEMPLATE: &str = "insert_template";
+const KW_IF_LINE: &str = "if_line";
+const KW_IF_FILE_LINE: &str = "if_file_line";
+const KW_IF_FILE: &str = "if_file";
+const KW_TEST: &str = "test";
+const KW_LINES: &str = "lines";
+const TEST_ARROW: &str = "=>";
+const KW_FORWARD_WORD_START: &str = "forward_word_start";
+const KW_BACK_WORD_START: &str = "back_word_start";
+const KW_FIND_NEXT: &str = "find_next";
+const KW_SELECT_WORD: &str = "select_word";
+const KW_PASTE_AFTER: &str = "paste_after";
+const KW_EXCHANGE_MARK: &str = "exchange_mark";
+const KW_DELETE_LINE: &str = "delete_line";
+const KW_PRINT: &str = "print";
+const KW_PRINT_CLIPBOARD: &str = "print_clipboard";
+const KW_EMIT: &str = "emit";
+const KW_EMIT_AFTER: &str = "emit_after";
+const KW_SPLIT_LINE: &str = "split_line";
+const KW_JOIN_NEXT: &str = "join_next";
+const KW_STORE_REGISTER: &str = "store_register";
+const KW_INSERT_REGISTER: &str = "insert_register";
+const KW_INCREMENT: &str = "increment";
+const KW_INSERT_COUNTER: &str = "insert_counter";
+const KW_LINE_DOWN: &str = "line_down";
+const KW_LINE_UP: &str = "line_up";
+const KW_LINE_START: &str = "line_start";
+const KW_LINE_END: &str = "line_end";
+const KW_HOLD: &str = "hold";
+const KW_HOLD_APPEND: &str = "hold_append";
+const KW_GET: &str = "get";
+const KW_GET_APPEND: &str = "get_append";
+const KW_EXCHANGE: &str = "exchange";
+const KW_AT_BEGIN: &str = "at_begin";
+const KW_AT_END: &str = "at_end";
+const KW_PERSIST: &str = "persist";
 
 #[derive(Debug, PartialEq)]
-enum ParseError {
+pub enum ParseError {
     ExpectedString,
     ExpectedNumber,
+    NegativeNumber(i32),
     ExpectedCommand,
+    ExpectedCharacter,
+    MismatchedTranslation,
+    UnclosedBlock,
+    ExpectedArrow,
+    ExpectedBlock,
+    InvalidAddress(AddressError),
     UnexpectedToken(Token),
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::ExpectedString => write!(f, "Expected a string."),
+            ParseError::ExpectedNumber => write!(f, "Expected a number."),
+            ParseError::NegativeNumber(num) => {
+                write!(f, "Expected a number of 0 or more, not {}.", num)
+            }
+            ParseError::ExpectedCommand => write!(f, "Expected a command."),
+            ParseError::ExpectedCharacter => write!(f, "Expected a string of one character."),
+            ParseError::MismatchedTranslation => {
+                write!(f, "Expected strings of equal length to translate between.")
+            }
+            ParseError::UnclosedBlock => write!(f, "Expected a `}}` closing the block."),
+            ParseError::ExpectedArrow => write!(f, "Expected `=>` between test input and output."),
+            ParseError::ExpectedBlock => write!(f, "Expected a `{{` block after `at_begin` or `at_end`."),
+            ParseError::InvalidAddress(err) => write!(f, "{}", err),
+            ParseError::UnexpectedToken(Token::WORD(info, word)) => {
+                write!(f, "Unknown command `{}` on line {}.", word, info.line())
+            }
+            ParseError::UnexpectedToken(token) => {
+                write!(f, "Unexpected {:?} on line {}.", token, token.info().line())
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
 fn expect_string(tokenizer: &mut Tokenizer) -> Result<String, ParseError> {
-    if let Some(Token::STRING(info, text)) = tokenizer.next() {
+    if let Some(Token::STRING(_, text)) = tokenizer.next() {
         Ok(text)
     } else {
         Err(ParseError::ExpectedString)
     }
 }
 
+/// Reads a string argument if one follows.
+fn optional_string(tokenizer: &mut Tokenizer) -> Option<String> {
+    match tokenizer.next() {
+        Some(Token::STRING(_, text)) => Some(text),
+        Some(token) => {
+            tokenizer.push_back(token);
+            None
+        }
+        None => None,
+    }
+}
+
 fn expect_number(tokenizer: &mut Tokenizer) -> Result<i32, ParseError> {
-    if let Some(Token::NUM(info, num)) = tokenizer.next() {
+    if let Some(Token::NUM(_, num)) = tokenizer.next() {
         Ok(num)
     } else {
         Err(ParseError::ExpectedNumber)
     }
 }
 
-fn parse_cmd(tokenizer: &mut Tokenizer) -> Result<Option<Cmd>, ParseError> {
-    if let Some(token) = tokenizer.next() {
-        let cmd = match token {
-            Token::WORD(info, word) => match word.as_ref() {
-                KW_FORWARD => Cmd::Forward,
-                KW_BACK => Cmd::Back,
-                KW_FORWARD_WORD => Cmd::ForwardWord,
-                KW_BACK_WORD => Cmd::BackWord,
-                KW_TRANSPOSE => Cmd::Transpose,
-                KW_TRANSPOSE_CHAR => Cmd::TransposeCharacter,
-                KW_UPCASE_CHAR => Cmd::UpcaseCharacter,
-                KW_DOWNCASE_CHAR => Cmd::DowncaseCharacter,
-                KW_UPCASE_LINE => Cmd::UpcaseLine,
-                KW_COPY_LINE => Cmd::CopyLine,
-                KW_TRIM_LINE => Cmd::TrimLine,
-                KW_LTRIM_LINE => Cmd::LTrimLine,
-                KW_RTRIM_LINE => Cmd::RTrimLine,
-                KW_UPCASE_CLIPBOARD => Cmd::UpcaseClipboard,
-                KW_TRIM_CLIPBOARD => Cmd::TrimClipboard,
-                KW_LTRIM_CLIPBOARD => Cmd::LeftTrimClipboard,
-                KW_RTRIM_CLIPBOARD => Cmd::RightTrimClipboard,
-                KW_DOWNCASE_CLIPBOARD => Cmd::DowncaseClipboard,
-                KW_DOWNCASE_LINE => Cmd::DowncaseLine,
-                KW_SENTENCE_CASE_CLIPBOARD => Cmd::SentencecaseClipboard,
-                KW_TRANSPOSE_WORD => Cmd::TransposeWord,
-                KW_UPCASE_WORD => Cmd::UpcaseWord,
-                KW_KILL_WORD => Cmd::KillWord,
-                KW_RKILL_WORD => Cmd::RKillWord,
-                KW_KILL_FULL_WORD => Cmd::KillFullWord,
-                KW_SENTENCECASE_WORD => Cmd::SentenceCaseWord,
-                KW_DOWNCASE_WORD => Cmd::DowncaseWord,
-                KW_NEXTLINE => Cmd::NextLine,
-                KW_KILL_LINE => Cmd::KillLine,
-                KW_RKILL_LINE => Cmd::RKillLine,
-                KW_MARK => Cmd::Mark,
-                KW_COPY => Cmd::Copy,
-                KW_CUT => Cmd::Cut,
-                KW_HOME => Cmd::Home,
-                KW_END => Cmd::End,
-                KW_LAST => Cmd::Last,
-                KW_PASTE => Cmd::Paste,
-                KW_DELETE => Cmd::Delete,
-                KW_DELETEBEFORE => Cmd::DeleteBefore,
-                KW_UPCASE => Cmd::UpcaseRegion,
-                KW_DOWNCASE => Cmd::DowncaseRegion,
-                KW_KILL => Cmd::Kill,
-                KW_TRUNCATE_BY => {
-                    let amount = expect_number(tokenizer)? as usize; // TODO: fix me
-                    Cmd::TruncateBy(amount)
+/// Expects a number that counts or positions something, which can not be negative.
+fn expect_count(tokenizer: &mut Tokenizer) -> Result<usize, ParseError> {
+    let num = expect_number(tokenizer)?;
+    if num < 0 {
+        return Err(ParseError::NegativeNumber(num));
+    }
+    Ok(num as usize)
+}
+
+fn expect_character(tokenizer: &mut Tokenizer) -> Result<char, ParseError> {
+    let text = expect_string(tokenizer)?;
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(ParseError::ExpectedCharacter),
+    }
+}
+
+fn expect_cmd(tokenizer: &mut Tokenizer) -> Result<Cmd, ParseError> {
+    match tokenizer.next() {
+        Some(token) => parse_token(token, tokenizer),
+        None => Err(ParseError::ExpectedCommand),
+    }
+}
+
+fn parse_block(tokenizer: &mut Tokenizer) -> Result<Vec<Cmd>, ParseError> {
+    let mut cmds = Vec::new();
+    loop {
+        match tokenizer.next() {
+            Some(Token::RBRACE(_)) => return Ok(cmds),
+            Some(token) => cmds.push(parse_token(token, tokenizer)?),
+            None => return Err(ParseError::UnclosedBlock),
+        }
+    }
+}
+
+fn parse_translation(tokenizer: &mut Tokenizer) -> Result<HashMap<char, char>, ParseError> {
+    let from = expect_string(tokenizer)?;
+    let to = expect_string(tokenizer)?;
+    if from.chars().count() != to.chars().count() {
+        return Err(ParseError::MismatchedTranslation);
+    }
+    Ok(from.chars().zip(to.chars()).collect())
+}
+
+fn parse_token(token: Token, tokenizer: &mut Tokenizer) -> Result<Cmd, ParseError> {
+    let cmd = match token {
+        Token::WORD(info, word) => match word.as_ref() {
+            KW_FORWARD => Cmd::Forward,
+            KW_BACK => Cmd::Back,
+            KW_FORWARD_WORD => Cmd::ForwardWord,
+            KW_BACK_WORD => Cmd::BackWord,
+            KW_TRANSPOSE => Cmd::Transpose,
+            KW_TRANSPOSE_CHAR => Cmd::TransposeCharacter,
+            KW_UPCASE_CHAR => Cmd::UpcaseCharacter,
+            KW_DOWNCASE_CHAR => Cmd::DowncaseCharacter,
+            KW_UPCASE_LINE => Cmd::UpcaseLine,
+            KW_COPY_LINE => Cmd::CopyLine,
+            KW_TRIM_LINE => Cmd::TrimLine,
+            KW_LTRIM_LINE => Cmd::LTrimLine,
+            KW_RTRIM_LINE => Cmd::RTrimLine,
+            KW_UPCASE_CLIPBOARD => Cmd::UpcaseClipboard,
+            KW_TRIM_CLIPBOARD => Cmd::TrimClipboard,
+            KW_LTRIM_CLIPBOARD => Cmd::LeftTrimClipboard,
+            KW_RTRIM_CLIPBOARD => Cmd::RightTrimClipboard,
+            KW_DOWNCASE_CLIPBOARD => Cmd::DowncaseClipboard,
+            KW_DOWNCASE_LINE => Cmd::DowncaseLine,
+            KW_SENTENCE_CASE_CLIPBOARD => Cmd::SentencecaseClipboard,
+            KW_TRANSPOSE_WORD => Cmd::TransposeWord,
+            KW_UPCASE_WORD => Cmd::UpcaseWord,
+            KW_KILL_WORD => Cmd::KillWord,
+            KW_RKILL_WORD => Cmd::RKillWord,
+            KW_KILL_FULL_WORD => Cmd::KillFullWord,
+            KW_SENTENCECASE_WORD => Cmd::SentenceCaseWord,
+            KW_DOWNCASE_WORD => Cmd::DowncaseWord,
+            KW_NEXTLINE => Cmd::NextLine,
+            KW_KILL_LINE => Cmd::KillLine,
+            KW_RKILL_LINE => Cmd::RKillLine,
+            KW_MARK => Cmd::Mark,
+            KW_COPY => Cmd::Copy,
+            KW_CUT => Cmd::Cut,
+            KW_HOME => Cmd::Home,
+            KW_END => Cmd::End,
+            KW_LAST => Cmd::Last,
+            KW_PASTE => Cmd::Paste,
+            KW_DELETE => Cmd::Delete,
+            KW_DELETEBEFORE => Cmd::DeleteBefore,
+            KW_UPCASE => Cmd::UpcaseRegion,
+            KW_DOWNCASE => Cmd::DowncaseRegion,
+            KW_KILL => Cmd::Kill,
+            KW_FORWARD_WORD_START => Cmd::ForwardWordStart,
+            KW_BACK_WORD_START => Cmd::BackWordStart,
+            KW_SELECT_WORD => Cmd::SelectWord,
+            KW_PASTE_AFTER => Cmd::PasteAfter,
+            KW_EXCHANGE_MARK => Cmd::ExchangeMark,
+            KW_DELETE_LINE => Cmd::DeleteLine,
+            KW_PRINT => Cmd::Print,
+            KW_PRINT_CLIPBOARD => Cmd::PrintClipboard,
+            KW_SPLIT_LINE => Cmd::SplitLine,
+            KW_JOIN_NEXT => Cmd::JoinNext {
+                separator: optional_string(tokenizer).unwrap_or_default(),
+            },
+            KW_EMIT => Cmd::Emit {
+                what: expect_string(tokenizer)?,
+            },
+            KW_EMIT_AFTER => Cmd::EmitAfter {
+                what: expect_string(tokenizer)?,
+            },
+            KW_LINE_DOWN => Cmd::ForwardLine,
+            KW_LINE_UP => Cmd::BackLine,
+            KW_LINE_START => Cmd::LineStart,
+            KW_LINE_END => Cmd::LineEnd,
+            KW_HOLD => Cmd::Hold,
+            KW_HOLD_APPEND => Cmd::HoldAppend,
+            KW_GET => Cmd::Get,
+            KW_GET_APPEND => Cmd::GetAppend,
+            KW_EXCHANGE => Cmd::Exchange,
+            KW_STORE_REGISTER => Cmd::StoreRegister {
+                name: expect_string(tokenizer)?,
+            },
+            KW_INSERT_REGISTER => Cmd::InsertRegister {
+                name: expect_string(tokenizer)?,
+            },
+            KW_INCREMENT => Cmd::Increment {
+                name: expect_string(tokenizer)?,
+            },
+            KW_INSERT_COUNTER => Cmd::InsertCounter {
+                name: expect_string(tokenizer)?,
+            },
+            KW_TRUNCATE_BY => {
+                let amount = expect_count(tokenizer)?;
+                Cmd::TruncateBy(amount)
+            }
+            KW_GOTO => Cmd::Goto(expect_count(tokenizer)?),
+            KW_REPEAT => {
+                let times = expect_count(tokenizer)?;
+                Cmd::Repeat {
+                    times,
+                    cmd: Box::new(expect_cmd(tokenizer)?),
                 }
-                KW_REPEAT => {
-                    let times = expect_number(tokenizer)? as usize; // TODO: fix me
-                    let cmd = parse_cmd(tokenizer)?;
-                    if let Some(cmd) = cmd {
-                        Cmd::Repeat {
-                            times,
-                            cmd: Box::new(cmd),
-                        }
-                    } else {
-                        return Err(ParseError::ExpectedCommand);
-                    }
+            }
+            KW_INSERT => Cmd::Insert {
+                what: expect_string(tokenizer)?,
+            },
+            KW_FIND => Cmd::Find {
+                what: expect_character(tokenizer)?,
+            },
+            KW_FIND_NEXT => Cmd::FindNext {
+                what: expect_character(tokenizer)?,
+            },
+            KW_RFIND => Cmd::RFind {
+                what: expect_character(tokenizer)?,
+            },
+            KW_TRANSLATE => Cmd::Translate {
+                table: parse_translation(tokenizer)?,
+            },
+            KW_INSERT_TEMPLATE => Cmd::InsertTemplate {
+                template: expect_string(tokenizer)?,
+            },
+            KW_IF_LINE | KW_IF_FILE_LINE | KW_IF_FILE => {
+                let condition = match word.as_ref() {
+                    KW_IF_LINE => Condition::Line(expect_count(tokenizer)?),
+                    KW_IF_FILE_LINE => Condition::FileLine(expect_count(tokenizer)?),
+                    _ => Condition::File(expect_string(tokenizer)?),
+                };
+                Cmd::If {
+                    condition,
+                    cmd: Box::new(expect_cmd(tokenizer)?),
                 }
-                KW_INSERT => Cmd::Insert {
-                    what: expect_string(tokenizer)?,
-                },
-                // KW_FIND => {
-                //     let text = expect_string(tokenizer).expect("Expected a string.");
-                //     cmds.push(Cmd::Find{what: text});
-                // }
-                // KW_RFIND => {
-                //     let text = expect_string(tokenizer).expect("Expected a string.");
-                //     cmds.push(Cmd::RFind{what: text});
-                // }
-                _ => return Err(ParseError::UnexpectedToken(Token::WORD(info, word))),
+            }
+            KW_SUBSTITUTE | KW_SUBSTITUTE_ALL => Cmd::Substitute {
+                from: expect_string(tokenizer)?,
+                to: expect_string(tokenizer)?,
+                all: word == KW_SUBSTITUTE_ALL,
             },
-            _ => return Err(ParseError::UnexpectedToken(token)),
+            _ => return Err(ParseError::UnexpectedToken(Token::WORD(info, word))),
+        },
+        Token::LBRACE(_) => Cmd::Block(parse_block(tokenizer)?),
+        _ => return Err(ParseError::UnexpectedToken(token)),
+    };
+    Ok(cmd)
+}
+
+/// An inline test case, `test "input" => "expected output"`.
+#[derive(Debug, PartialEq)]
+pub struct TestCase {
+    pub line: usize,
+    pub input: String,
+    pub expected: String,
+}
+
+/// A program together with its inline test cases.
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    /// Top level commands with the program line they start on.
+    pub cmds: Vec<(usize, Cmd)>,
+    pub tests: Vec<TestCase>,
+    /// The lines the program edits, from a `lines "address"` directive.
+    pub address: Option<Address>,
+    /// Commands run once before the first line, from `at_begin { }` blocks.
+    pub begin: Vec<Cmd>,
+    /// Commands run once after the last line, from `at_end { }` blocks.
+    pub end: Vec<Cmd>,
+    /// The clipboard, registers and counters carry over from line to line,
+    /// from a `persist` directive.
+    pub persist: bool,
+}
+
+fn parse_test(line: usize, tokenizer: &mut Tokenizer) -> Result<TestCase, ParseError> {
+    let input = expect_string(tokenizer)?;
+    match tokenizer.next() {
+        Some(Token::WORD(_, ref arrow)) if arrow == TEST_ARROW => (),
+        _ => return Err(ParseError::ExpectedArrow),
+    }
+    Ok(TestCase {
+        line,
+        input,
+        expected: expect_string(tokenizer)?,
+    })
+}
+
+fn parse_address(tokenizer: &mut Tokenizer, program: &Program) -> Result<Address, ParseError> {
+    let text = expect_string(tokenizer)?;
+    if program.address.is_some() {
+        return Err(ParseError::InvalidAddress(AddressError {
+            address: text,
+            reason: "a program has at most one `lines` directive".to_owned(),
+        }));
+    }
+    text.parse().map_err(ParseError::InvalidAddress)
+}
+
+fn expect_block(tokenizer: &mut Tokenizer) -> Result<Vec<Cmd>, ParseError> {
+    match tokenizer.next() {
+        Some(Token::LBRACE(_)) => parse_block(tokenizer),
+        _ => Err(ParseError::ExpectedBlock),
+    }
+}
+
+/// Parses a program and the test cases, directives and `at_begin` and
+/// `at_end` blocks written among its top level commands.
+pub fn parse_program(tokenizer: &mut Tokenizer) -> Result<Program, ParseError> {
+    let mut program = Program {
+        cmds: Vec::new(),
+        tests: Vec::new(),
+        address: None,
+        begin: Vec::new(),
+        end: Vec::new(),
+        persist: false,
+    };
+    while let Some(token) = tokenizer.next() {
+        let line = token.info().line();
+        match token {
+            Token::WORD(_, ref word) if word == KW_TEST => {
+                program.tests.push(parse_test(line, tokenizer)?)
+            }
+            Token::WORD(_, ref word) if word == KW_LINES => {
+                program.address = Some(parse_address(tokenizer, &program)?)
+            }
+            Token::WORD(_, ref word) if word == KW_PERSIST => program.persist = true,
+            Token::WORD(_, ref word) if word == KW_AT_BEGIN => {
+                program.begin.extend(expect_block(tokenizer)?)
+            }
+            Token::WORD(_, ref word) if word == KW_AT_END => {
+                program.end.extend(expect_block(tokenizer)?)
+            }
+            token => program.cmds.push((line, parse_token(token, tokenizer)?)),
+        }
+    }
+    Ok(program)
+}
+
+pub fn parse(tokenizer: &mut Tokenizer) -> Result<Vec<Cmd>, ParseError> {
+    let located = parse_located(tokenizer)?;
+    Ok(located.into_iter().map(|(_, cmd)| cmd).collect())
+}
+
+/// Parses a program, keeping the program line each top level command starts
+/// on. Test cases, directives and `at_begin` and `at_end` blocks are skipped.
+pub fn parse_located(tokenizer: &mut Tokenizer) -> Result<Vec<(usize, Cmd)>, ParseError> {
+    Ok(parse_program(tokenizer)?.cmds)
+}
+
+/// Quotes `text` as a program string.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Prints a program with one top level command per line.
+pub fn unparse(program: &[Cmd]) -> String {
+    program.iter().map(|cmd| format!("{}\n", cmd)).collect()
+}
+
+/// Prints a command in program syntax, so that parsing the output gives the command back.
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self {
+            Cmd::Back => KW_BACK,
+            Cmd::Forward => KW_FORWARD,
+            Cmd::ForwardWord => KW_FORWARD_WORD,
+            Cmd::BackWord => KW_BACK_WORD,
+            Cmd::Home => KW_HOME,
+            Cmd::End => KW_END,
+            Cmd::Last => KW_LAST,
+            Cmd::Delete => KW_DELETE,
+            Cmd::DeleteBefore => KW_DELETEBEFORE,
+            Cmd::TransposeCharacter => KW_TRANSPOSE_CHAR,
+            Cmd::UpcaseCharacter => KW_UPCASE_CHAR,
+            Cmd::DowncaseCharacter => KW_DOWNCASE_CHAR,
+            Cmd::CopyLine => KW_COPY_LINE,
+            Cmd::KillWord => KW_KILL_WORD,
+            Cmd::RKillWord => KW_RKILL_WORD,
+            Cmd::KillFullWord => KW_KILL_FULL_WORD,
+            Cmd::TransposeWord => KW_TRANSPOSE_WORD,
+            Cmd::UpcaseWord => KW_UPCASE_WORD,
+            Cmd::DowncaseWord => KW_DOWNCASE_WORD,
+            Cmd::SentenceCaseWord => KW_SENTENCECASE_WORD,
+            Cmd::Mark => KW_MARK,
+            Cmd::UpcaseRegion => KW_UPCASE,
+            Cmd::DowncaseRegion => KW_DOWNCASE,
+            Cmd::Transpose => KW_TRANSPOSE,
+            Cmd::Copy => KW_COPY,
+            Cmd::Paste => KW_PASTE,
+            Cmd::Cut => KW_CUT,
+            Cmd::UpcaseClipboard => KW_UPCASE_CLIPBOARD,
+            Cmd::DowncaseClipboard => KW_DOWNCASE_CLIPBOARD,
+            Cmd::SentencecaseClipboard => KW_SENTENCE_CASE_CLIPBOARD,
+            Cmd::LeftTrimClipboard => KW_LTRIM_CLIPBOARD,
+            Cmd::RightTrimClipboard => KW_RTRIM_CLIPBOARD,
+            Cmd::TrimClipboard => KW_TRIM_CLIPBOARD,
+            Cmd::Kill => KW_KILL,
+            Cmd::KillLine => KW_KILL_LINE,
+            Cmd::RKillLine => KW_RKILL_LINE,
+            Cmd::TrimLine => KW_TRIM_LINE,
+            Cmd::LTrimLine => KW_LTRIM_LINE,
+            Cmd::RTrimLine => KW_RTRIM_LINE,
+            Cmd::UpcaseLine => KW_UPCASE_LINE,
+            Cmd::DowncaseLine => KW_DOWNCASE_LINE,
+            Cmd::NextLine => KW_NEXTLINE,
+            Cmd::ForwardWordStart => KW_FORWARD_WORD_START,
+            Cmd::BackWordStart => KW_BACK_WORD_START,
+            Cmd::SelectWord => KW_SELECT_WORD,
+            Cmd::PasteAfter => KW_PASTE_AFTER,
+            Cmd::ExchangeMark => KW_EXCHANGE_MARK,
+            Cmd::DeleteLine => KW_DELETE_LINE,
+            Cmd::Print => KW_PRINT,
+            Cmd::PrintClipboard => KW_PRINT_CLIPBOARD,
+            Cmd::SplitLine => KW_SPLIT_LINE,
+            Cmd::ForwardLine => KW_LINE_DOWN,
+            Cmd::BackLine => KW_LINE_UP,
+            Cmd::LineStart => KW_LINE_START,
+            Cmd::LineEnd => KW_LINE_END,
+            Cmd::Hold => KW_HOLD,
+            Cmd::HoldAppend => KW_HOLD_APPEND,
+            Cmd::Get => KW_GET,
+            Cmd::GetAppend => KW_GET_APPEND,
+            Cmd::Exchange => KW_EXCHANGE,
+            Cmd::StoreRegister { name } => {
+                return write!(f, "{} {}", KW_STORE_REGISTER, quote(name))
+            }
+            Cmd::InsertRegister { name } => {
+                return write!(f, "{} {}", KW_INSERT_REGISTER, quote(name))
+            }
+            Cmd::Increment { name } => return write!(f, "{} {}", KW_INCREMENT, quote(name)),
+            Cmd::InsertCounter { name } => {
+                return write!(f, "{} {}", KW_INSERT_COUNTER, quote(name))
+            }
+            Cmd::JoinNext { separator } if separator.is_empty() => KW_JOIN_NEXT,
+            Cmd::JoinNext { separator } => {
+                return write!(f, "{} {}", KW_JOIN_NEXT, quote(separator))
+            }
+            Cmd::Emit { what } => return write!(f, "{} {}", KW_EMIT, quote(what)),
+            Cmd::EmitAfter { what } => return write!(f, "{} {}", KW_EMIT_AFTER, quote(what)),
+            Cmd::Goto(column) => return write!(f, "{} {}", KW_GOTO, column),
+            Cmd::TruncateBy(amount) => return write!(f, "{} {}", KW_TRUNCATE_BY, amount),
+            Cmd::Find { what } => return write!(f, "{} {}", KW_FIND, quote(&what.to_string())),
+            Cmd::FindNext { what } => {
+                return write!(f, "{} {}", KW_FIND_NEXT, quote(&what.to_string()))
+            }
+            Cmd::RFind { what } => return write!(f, "{} {}", KW_RFIND, quote(&what.to_string())),
+            Cmd::Insert { what } => return write!(f, "{} {}", KW_INSERT, quote(what)),
+            Cmd::Repeat { times, cmd } => return write!(f, "{} {} {}", KW_REPEAT, times, cmd),
+            Cmd::Translate { table } => {
+                let mut pairs: Vec<(&char, &char)> = table.iter().collect();
+                pairs.sort();
+                let from: String = pairs.iter().map(|(&from, _)| from).collect();
+                let to: String = pairs.iter().map(|(_, &to)| to).collect();
+                return write!(f, "{} {} {}", KW_TRANSLATE, quote(&from), quote(&to));
+            }
+            Cmd::Substitute { from, to, all } => {
+                let keyword = if *all { KW_SUBSTITUTE_ALL } else { KW_SUBSTITUTE };
+                return write!(f, "{} {} {}", keyword, quote(from), quote(to));
+            }
+            Cmd::InsertTemplate { template } => {
+                return write!(f, "{} {}", KW_INSERT_TEMPLATE, quote(template))
+            }
+            Cmd::If { condition, cmd } => {
+                return match condition {
+                    Condition::Line(line) => write!(f, "{} {} {}", KW_IF_LINE, line, cmd),
+                    Condition::FileLine(line) => write!(f, "{} {} {}", KW_IF_FILE_LINE, line, cmd),
+                    Condition::File(file) => write!(f, "{} {} {}", KW_IF_FILE, quote(file), cmd),
+                }
+            }
+            Cmd::Block(cmds) => {
+                f.write_str("{")?;
+                for cmd in cmds {
+                    write!(f, " {}", cmd)?;
+                }
+                return f.write_str(" }");
+            }
         };
-        Ok(Some(cmd))
-    } else {
-        Ok(None)
+        f.write_str(keyword)
     }
 }
 
-pub fn parse(tokenizer: &mut Tokenizer) -> Option<Vec<Cmd>> {
-    let mut cmds = Vec::new();
-    loop {
-        let cmd = parse_cmd(tokenizer);
-        match cmd {
-            Ok(Some(cmd)) => cmds.push(cmd),
-            Ok(None) => break,
-            Err(err) => panic!("Invalid syntax {:?}.", err),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::TokenizerTrait;
+
+    fn parse_text(text: &str) -> Result<Vec<Cmd>, ParseError> {
+        parse(&mut text.chars().tokens())
+    }
+
+    #[test]
+    fn parse_blocks_and_arguments() {
+        let program = parse_text(r#"repeat 2 { forward find "," } translate "ab" "xy" goto 3"#);
+
+        let mut table = HashMap::new();
+        table.insert('a', 'x');
+        table.insert('b', 'y');
+        assert_eq!(
+            Ok(vec![
+                Cmd::Repeat {
+                    times: 2,
+                    cmd: Box::new(Cmd::Block(vec![Cmd::Forward, Cmd::Find { what: ',' }])),
+                },
+                Cmd::Translate { table },
+                Cmd::Goto(3),
+            ]),
+            program
+        );
+    }
+
+    #[test]
+    fn parse_begin_and_end_blocks() {
+        let text = "persist at_begin { insert \"a\" } end\n{ forward } at_end { home }\nat_begin { back }";
+        let program = parse_program(&mut text.chars().tokens()).unwrap();
+
+        assert!(program.persist);
+        assert_eq!(
+            vec![
+                Cmd::Insert {
+                    what: "a".to_owned()
+                },
+                Cmd::Back
+            ],
+            program.begin
+        );
+        assert_eq!(vec![Cmd::Home], program.end);
+        let cmds: Vec<Cmd> = program.cmds.into_iter().map(|(_, cmd)| cmd).collect();
+        assert_eq!(vec![Cmd::End, Cmd::Block(vec![Cmd::Forward])], cmds);
+        assert_eq!(
+            Err(ParseError::ExpectedBlock),
+            parse_program(&mut "at_begin home".chars().tokens())
+        );
+
+        // `end` stays the motion, even followed by a block.
+        let program = parse_program(&mut r#"end { insert "x" }"#.chars().tokens()).unwrap();
+        assert!(program.end.is_empty());
+        let cmds: Vec<Cmd> = program.cmds.into_iter().map(|(_, cmd)| cmd).collect();
+        let insert = Cmd::Insert {
+            what: "x".to_owned(),
+        };
+        assert_eq!(vec![Cmd::End, Cmd::Block(vec![insert])], cmds);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Err(ParseError::UnclosedBlock), parse_text("{ forward"));
+        assert_eq!(Err(ParseError::ExpectedCharacter), parse_text(r#"find "ab""#));
+        let lines = parse_program(&mut r#"lines "2,/x/" home lines "3""#.chars().tokens());
+        match lines {
+            Err(ParseError::InvalidAddress(err)) => assert_eq!("3", err.address),
+            lines => panic!("{:?}", lines),
         }
+        assert_eq!(Err(ParseError::MismatchedTranslation), parse_text(r#"translate "ab" "x""#));
+        assert_eq!(Err(ParseError::ExpectedCommand), parse_text("repeat 2"));
+        assert_eq!(Err(ParseError::NegativeNumber(-1)), parse_text("goto -1"));
+        assert_eq!(Err(ParseError::NegativeNumber(-2)), parse_text("repeat -2 forward"));
+        assert_eq!(Err(ParseError::NegativeNumber(-3)), parse_text("truncate_by -3"));
+        assert_eq!(Err(ParseError::NegativeNumber(-1)), parse_text("if_line -1 forward"));
+        assert_eq!(Err(ParseError::NegativeNumber(-4)), parse_text("if_file_line -4 forward"));
     }
 
-    if cmds.is_empty() {
-        None
-    } else {
-        println!("Cmds: {:?}", cmds);
-        Some(cmds)
+    #[test]
+    fn parse_inline_tests() {
+        let text = "upcase_line\ntest \"a\" => \"A\"\ntest \"b\" \"B\"";
+        assert_eq!(Err(ParseError::ExpectedArrow), parse_program(&mut text.chars().tokens()));
+
+        let program = parse_program(&mut text[..text.rfind('\n').unwrap()].chars().tokens());
+        assert_eq!(
+            Ok(Program {
+                cmds: vec![(1, Cmd::UpcaseLine)],
+                tests: vec![TestCase {
+                    line: 2,
+                    input: "a".to_owned(),
+                    expected: "A".to_owned(),
+                }],
+                address: None,
+                begin: Vec::new(),
+                end: Vec::new(),
+                persist: false,
+            }),
+            program
+        );
+    }
+
+    #[test]
+    fn printed_program_parses_back() {
+        let text = r#"mark forward_word{back_word cut} insert "say \"hi\" \\" rfind "\"" repeat 3 upcase_char substitute_all "a" "b" if_file_line 1 { insert_template "{file}:" } emit_after "--" delete_line join_next join_next ", " split_line store_register "r" increment "n" hold_append exchange line_down line_end"#;
+        let program = parse_text(text).unwrap();
+        let printed: Vec<String> = program.iter().map(|cmd| cmd.to_string()).collect();
+
+        assert_eq!(
+            vec![
+                "mark",
+                "forward_word",
+                "{ back_word cut }",
+                r#"insert "say \"hi\" \\""#,
+                r#"rfind "\"""#,
+                "repeat 3 upcase_char",
+                r#"substitute_all "a" "b""#,
+                r#"if_file_line 1 { insert_template "{file}:" }"#,
+                r#"emit_after "--""#,
+                "delete_line",
+                "join_next",
+                r#"join_next ", ""#,
+                "split_line",
+                r#"store_register "r""#,
+                r#"increment "n""#,
+                "hold_append",
+                "exchange",
+                "line_down",
+                "line_end",
+            ],
+            printed
+        );
+        assert_eq!(program, parse_text(&printed.join("\n")).unwrap());
     }
 }
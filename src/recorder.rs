@@ -0,0 +1,133 @@
+//! Records a program by editing a sample line in the terminal with emacs keys.
+use cmd::{Cmd, LineState};
+use emacs;
+use emacs::Key;
+use parser;
+use std::error::Error;
+use std::io::Write;
+use termion;
+use termion::event::Key as TermKey;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+
+/// The commands run so far on a sample line.
+pub struct Recorder {
+    state: LineState,
+    cmds: Vec<Cmd>,
+}
+
+impl Recorder {
+    pub fn new(sample: &str) -> Recorder {
+        Recorder {
+            state: LineState::new(sample),
+            cmds: Vec::new(),
+        }
+    }
+
+    /// Runs the command bound to `key`. Returns false if the key is unbound.
+    pub fn press(&mut self, key: Key) -> bool {
+        match emacs::command(key) {
+            Some(cmd) => {
+                cmd.eval(&mut self.state);
+                self.cmds.push(cmd);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The recorded commands as a program, one command per line.
+    pub fn into_program(self) -> String {
+        parser::unparse(&emacs::compact(self.cmds))
+    }
+}
+
+fn emacs_key(key: TermKey) -> Option<Key> {
+    let key = match key {
+        TermKey::Null => Key::Ctrl(' '),
+        TermKey::Ctrl(c) => Key::Ctrl(c),
+        TermKey::Alt('\x7f') => Key::MetaBackspace,
+        TermKey::Alt(c) => Key::Meta(c),
+        TermKey::Char(c) => Key::Char(c),
+        TermKey::Backspace => Key::Backspace,
+        TermKey::Delete => Key::Delete,
+        TermKey::Left => Key::Left,
+        TermKey::Right => Key::Right,
+        TermKey::Home => Key::Home,
+        TermKey::End => Key::End,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn draw<W: Write>(screen: &mut W, recorder: &Recorder, message: &str) -> Result<(), Box<dyn Error>> {
+    let clipboard: String = match recorder.state.clipboard.last() {
+        Some(text) => text.iter().collect(),
+        None => String::new(),
+    };
+    write!(
+        screen,
+        "\r{}{}    [clipboard {:?}] {}",
+        termion::clear::CurrentLine,
+        recorder.state.render(),
+        clipboard,
+        message
+    )?;
+    screen.flush()?;
+    Ok(())
+}
+
+/// Lets the user edit `sample` in the terminal until RET is pressed and
+/// returns the recorded program. C-g aborts the recording.
+pub fn record(sample: &str) -> Result<String, Box<dyn Error>> {
+    let tty = termion::get_tty()?;
+    let mut screen = tty.try_clone()?.into_raw_mode()?;
+    let mut recorder = Recorder::new(sample);
+
+    draw(&mut screen, &recorder, "RET to finish, C-g to abort")?;
+    for key in tty.keys() {
+        let message = match key? {
+            TermKey::Char('\n') => break,
+            TermKey::Ctrl('g') | TermKey::Ctrl('c') => {
+                write!(screen, "\r\n")?;
+                return Err("Recording aborted.".into());
+            }
+            key => match emacs_key(key) {
+                Some(key) if recorder.press(key) => "",
+                _ => "unbound key",
+            },
+        };
+        draw(&mut screen, &recorder, message)?;
+    }
+    write!(screen, "\r\n")?;
+
+    Ok(recorder.into_program())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keys_as_program() {
+        let mut recorder = Recorder::new("    Back,");
+        let keys = vec![
+            Key::Ctrl(' '),
+            Key::Meta('f'),
+            Key::Meta('b'),
+            Key::Ctrl('w'),
+            Key::Ctrl('e'),
+            Key::Backspace,
+            Key::Char(';'),
+            Key::Ctrl('x'),
+        ];
+        let bound: Vec<bool> = keys.into_iter().map(|key| recorder.press(key)).collect();
+
+        assert_eq!(vec![true, true, true, true, true, true, true, false], bound);
+        assert_eq!("^Back;|", recorder.state.render());
+        assert_eq!(
+            "mark\nforward_word\nback_word\ncut\nend\nrdelete\ninsert \";\"\n",
+            recorder.into_program()
+        );
+    }
+}
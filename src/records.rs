@@ -0,0 +1,344 @@
+//! Splitting input into records at separators other than newlines.
+use encoding::Decoding;
+use regex::bytes;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, Read};
+
+/// What ends the records of the input.
+#[derive(Debug, Clone, Default)]
+pub enum Separator {
+    /// A newline or `\r\n`.
+    #[default]
+    Newline,
+    /// A string such as NUL or `;`. It must not be empty.
+    String(String),
+    /// Anything the regular expression matches. The input is read whole
+    /// before it is split.
+    Regex(Regex),
+    /// One or more blank lines, so that every paragraph is a record. The
+    /// lines of a paragraph are joined with newlines.
+    Paragraph,
+    /// Nothing, so that the whole input but a final newline is one record.
+    Whole,
+}
+
+impl Separator {
+    pub fn regex(pattern: &str) -> Result<Separator, regex::Error> {
+        Regex::new(pattern).map(Separator::Regex)
+    }
+
+    /// The separator written after records whose own separator is not kept,
+    /// such as paragraphs and the lines a program adds.
+    pub fn output(&self) -> &str {
+        match self {
+            Separator::Newline | Separator::Regex(_) | Separator::Whole => "\n",
+            Separator::String(separator) => separator,
+            Separator::Paragraph => "\n\n",
+        }
+    }
+}
+
+/// The byte order mark some editors put at the start of UTF-8 files.
+pub const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// A record of the input and the separator that ended it.
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub text: String,
+    /// Empty for a last record without a separator, `None` where the input
+    /// separator is not kept, as for paragraphs.
+    pub ending: Option<String>,
+    /// Decoded byte by byte, to be encoded back with `encoding::to_bytes`.
+    pub raw: bool,
+}
+
+/// Input with bytes that were read put back in front of it.
+struct PushBack<R> {
+    pushed: Vec<u8>,
+    input: R,
+}
+
+impl<R: BufRead> Read for PushBack<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.fill_buf()?.read(buf)?;
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl<R: BufRead> BufRead for PushBack<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pushed.is_empty() {
+            self.input.fill_buf()
+        } else {
+            Ok(&self.pushed)
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if self.pushed.is_empty() {
+            self.input.consume(amount);
+        } else {
+            self.pushed.drain(..amount);
+        }
+    }
+}
+
+/// The records of an input.
+pub struct Records<'a, R> {
+    input: PushBack<R>,
+    separator: &'a Separator,
+    decoding: Decoding,
+    /// Records split off the whole input, once it is read.
+    split: Option<VecDeque<Record>>,
+}
+
+/// Strips a trailing `\n` or `\r\n`, returning it.
+fn strip_newline(line: &mut Vec<u8>) -> &'static str {
+    if line.last() != Some(&b'\n') {
+        return "";
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+        return "\r\n";
+    }
+    "\n"
+}
+
+impl<'a, R: BufRead> Records<'a, R> {
+    pub fn new(input: R, separator: &'a Separator, decoding: Decoding) -> Records<'a, R> {
+        Records {
+            input: PushBack {
+                pushed: Vec::new(),
+                input,
+            },
+            separator,
+            decoding,
+            split: None,
+        }
+    }
+
+    fn record(&self, text: Vec<u8>, ending: Option<&[u8]>) -> io::Result<Record> {
+        let (text, raw) = self.decoding.decode(text)?;
+        let (ending, raw) = match ending {
+            Some(ending) => {
+                let (ending, raw_ending) = self.decoding.decode(ending.to_vec())?;
+                (Some(ending), raw || raw_ending)
+            }
+            None => (None, raw),
+        };
+        Ok(Record { text, ending, raw })
+    }
+
+    /// Skips a byte order mark at the start of the input, returning whether
+    /// there was one.
+    pub fn skip_bom(&mut self) -> io::Result<bool> {
+        // The input may come in pieces shorter than the mark. Reading stops
+        // as soon as it cannot be one, so as not to wait on a terminal.
+        let mut start = Vec::new();
+        while start.len() < BOM.len() && BOM.starts_with(&start) {
+            let count = {
+                let available = self.input.fill_buf()?;
+                let count = available.len().min(BOM.len() - start.len());
+                start.extend_from_slice(&available[..count]);
+                count
+            };
+            if count == 0 {
+                break;
+            }
+            self.input.consume(count);
+        }
+        if start == BOM {
+            return Ok(true);
+        }
+        self.input.pushed = start;
+        Ok(false)
+    }
+
+    fn line(&mut self) -> io::Result<Option<(Vec<u8>, &'static str)>> {
+        let mut line = Vec::new();
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let ending = strip_newline(&mut line);
+        Ok(Some((line, ending)))
+    }
+
+    fn up_to(&mut self, separator: &str) -> io::Result<Option<Record>> {
+        let bytes = separator.as_bytes();
+        let last = bytes[bytes.len() - 1];
+        let mut record = Vec::new();
+        while self.input.read_until(last, &mut record)? > 0 {
+            if record.ends_with(bytes) {
+                record.truncate(record.len() - bytes.len());
+                return self.record(record, Some(bytes)).map(Some);
+            }
+        }
+        if record.is_empty() {
+            return Ok(None);
+        }
+        self.record(record, Some(b"")).map(Some)
+    }
+
+    fn paragraph(&mut self) -> io::Result<Option<Record>> {
+        let mut record = Vec::new();
+        while let Some((line, _)) = self.line()? {
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                if record.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if !record.is_empty() {
+                record.push(b'\n');
+            }
+            record.extend_from_slice(&line);
+        }
+        if record.is_empty() {
+            return Ok(None);
+        }
+        self.record(record, None).map(Some)
+    }
+
+    /// Splits `input` at the matches of `regex`, keeping every match as the
+    /// ending of the record before it.
+    fn split_regex(&self, input: &[u8], regex: &Regex) -> io::Result<VecDeque<Record>> {
+        let regex = bytes::Regex::new(regex.as_str())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let mut records = VecDeque::new();
+        let mut start = 0;
+        for separator in regex.find_iter(input) {
+            let text = input[start..separator.start()].to_vec();
+            records.push_back(self.record(text, Some(separator.as_bytes()))?);
+            start = separator.end();
+        }
+        // A separator at the end does not start another record.
+        if start < input.len() {
+            records.push_back(self.record(input[start..].to_vec(), Some(b""))?);
+        }
+        Ok(records)
+    }
+
+    /// Reads the whole input and splits it with `regex`, or not at all.
+    fn split_off(&mut self, regex: Option<&Regex>) -> io::Result<Option<Record>> {
+        if self.split.is_none() {
+            let mut bytes = Vec::new();
+            self.input.read_to_end(&mut bytes)?;
+            let records = match regex {
+                Some(regex) => self.split_regex(&bytes, regex)?,
+                None if bytes.is_empty() => VecDeque::new(),
+                None => {
+                    let ending = strip_newline(&mut bytes);
+                    VecDeque::from(vec![self.record(bytes, Some(ending.as_bytes()))?])
+                }
+            };
+            self.split = Some(records);
+        }
+        Ok(self.split.as_mut().and_then(|records| records.pop_front()))
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match self.separator {
+            Separator::Newline => match self.line()? {
+                Some((line, ending)) => self.record(line, Some(ending.as_bytes())).map(Some),
+                None => Ok(None),
+            },
+            Separator::String(separator) => self.up_to(separator),
+            Separator::Paragraph => self.paragraph(),
+            Separator::Regex(regex) => self.split_off(Some(regex)),
+            Separator::Whole => self.split_off(None),
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for Records<'a, R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        self.next_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &[u8], separator: Separator) -> Vec<String> {
+        Records::new(input, &separator, Decoding::Utf8)
+            .map(|record| record.unwrap().text)
+            .collect()
+    }
+
+    fn endings(input: &[u8], separator: Separator) -> Vec<Option<String>> {
+        Records::new(input, &separator, Decoding::Utf8)
+            .map(|record| record.unwrap().ending)
+            .collect()
+    }
+
+    #[test]
+    fn split_records() {
+        assert_eq!(
+            vec!["a", "b", "", "c\r"],
+            records(b"a\r\nb\n\nc\r", Separator::Newline)
+        );
+        let nul = Separator::String("\0".to_owned());
+        assert_eq!(vec!["a b", "c\n"], records(b"a b\0c\n\0", nul));
+        let arrow = Separator::String("->".to_owned());
+        assert_eq!(vec!["a-b", ">c", "d"], records(b"a-b->>c->d", arrow));
+        let regex = Separator::regex(r"\s*;\s*").unwrap();
+        assert_eq!(vec!["a", "b", "", "c"], records(b"a ; b;;c;\n", regex));
+        assert_eq!(
+            vec!["a\nb", "c"],
+            records(b"\n\na\nb\n  \n\n\nc\n", Separator::Paragraph)
+        );
+        assert_eq!(vec!["a\n\nb\n"], records(b"a\n\nb\n\n", Separator::Whole));
+        assert!(records(b"", Separator::Whole).is_empty());
+    }
+
+    #[test]
+    fn keep_endings_and_bom() {
+        let some = |ending: &str| Some(ending.to_owned());
+        assert_eq!(
+            vec![some("\r\n"), some("\n"), some("")],
+            endings(b"a\r\nb\nc", Separator::Newline)
+        );
+        let regex = Separator::regex(r"\s*;\s*").unwrap();
+        assert_eq!(vec![some(" ; "), some(";\n")], endings(b"a ; b;\n", regex));
+        assert_eq!(vec![some("\r\n")], endings(b"a\nb\r\n", Separator::Whole));
+        assert_eq!(vec![None], endings(b"a\n\n", Separator::Paragraph));
+
+        let mut records =
+            Records::new(&b"\xef\xbb\xbfa\n"[..], &Separator::Newline, Decoding::Utf8);
+        assert!(records.skip_bom().unwrap());
+        assert!(!records.skip_bom().unwrap());
+        assert_eq!("a", records.next().unwrap().unwrap().text);
+
+        // Input read a byte at a time, with and without a mark.
+        let input = io::BufReader::with_capacity(1, &b"\xef\xbb\xbfa\n"[..]);
+        let mut records = Records::new(input, &Separator::Newline, Decoding::Utf8);
+        assert!(records.skip_bom().unwrap());
+        assert_eq!("a", records.next().unwrap().unwrap().text);
+        let input = io::BufReader::with_capacity(1, "\u{fec0}a\nb".as_bytes());
+        let mut records = Records::new(input, &Separator::Newline, Decoding::Utf8);
+        assert!(!records.skip_bom().unwrap());
+        assert_eq!("\u{fec0}a", records.next().unwrap().unwrap().text);
+        assert_eq!("b", records.next().unwrap().unwrap().text);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let nul = Separator::String("\0".to_owned());
+        let mut records = Records::new(&b"a\0\xff\0"[..], &nul, Decoding::Utf8);
+        assert_eq!("a", records.next().unwrap().unwrap().text);
+        let err = records.next().unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        let regex = Separator::regex(";").unwrap();
+        let mut records = Records::new(&b"a;\xff;"[..], &regex, Decoding::PassThrough);
+        assert!(!records.next().unwrap().unwrap().raw);
+        assert!(records.next().unwrap().unwrap().raw);
+    }
+}
@@ -0,0 +1,143 @@
+//! Interactive editing of a single sample line, one program line at a time.
+//!
+//! Every accepted program line is kept in the session history, which can be
+//! saved as a program file.
+use cmd::{Cmd, LineState};
+use parser;
+use std::error::Error;
+use std::io::{BufRead, Write};
+use tokenizer::TokenizerTrait;
+
+const HELP: &str = "\
+Enter commands to run them on the line, for example `mark forward_word upcase`.
+:undo          Forget the last entered commands.
+:reset         Forget all entered commands.
+:history       Print the commands entered so far.
+:save FILE     Save the entered commands as a program file.
+:quit          End the session.";
+
+/// An editing session on a sample line.
+pub struct Session {
+    sample: String,
+    state: LineState,
+    history: Vec<(String, Vec<Cmd>)>,
+}
+
+impl Session {
+    pub fn new(sample: &str) -> Session {
+        Session {
+            sample: sample.to_owned(),
+            state: LineState::new(sample),
+            history: Vec::new(),
+        }
+    }
+
+    /// The entered program lines, in order.
+    pub fn program(&self) -> String {
+        let lines: Vec<&str> = self.history.iter().map(|(text, _)| text.as_ref()).collect();
+        lines.join("\n")
+    }
+
+    fn replay(&mut self) {
+        let mut state = LineState::new(&self.sample);
+        for (_, cmds) in &self.history {
+            cmds.iter().for_each(|cmd| cmd.eval(&mut state));
+        }
+        self.state = state;
+    }
+
+    fn show<W: Write>(&self, out: &mut W) -> Result<(), Box<dyn Error>> {
+        writeln!(out, "{}", self.state.render())?;
+        for (depth, text) in self.state.clipboard.iter().rev().enumerate() {
+            writeln!(out, "  clipboard[{}] = {:?}", depth, text.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+
+    /// Reads commands until end of input or `:quit`, redrawing the line after each.
+    pub fn run<C: BufRead, W: Write>(
+        &mut self,
+        commands: &mut C,
+        out: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        self.show(out)?;
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim();
+
+            let mut words = line.splitn(2, ' ');
+            match (words.next(), words.next()) {
+                (Some(""), None) => continue,
+                (Some(":quit"), None) | (Some(":q"), None) => return Ok(()),
+                (Some(":undo"), None) => {
+                    self.history.pop();
+                    self.replay();
+                }
+                (Some(":reset"), None) => {
+                    self.history.clear();
+                    self.replay();
+                }
+                (Some(":history"), None) => {
+                    writeln!(out, "{}", self.program())?;
+                    continue;
+                }
+                (Some(":save"), Some(path)) => {
+                    ::std::fs::write(path.trim(), self.program() + "\n")?;
+                    writeln!(out, "Saved {} lines to {}.", self.history.len(), path.trim())?;
+                    continue;
+                }
+                (Some(command), _) if command.starts_with(':') => {
+                    writeln!(out, "{}", HELP)?;
+                    continue;
+                }
+                _ => match parser::parse(&mut line.chars().tokens()) {
+                    Ok(cmds) => {
+                        cmds.iter().for_each(|cmd| cmd.eval(&mut self.state));
+                        self.history.push((line.to_owned(), cmds));
+                    }
+                    Err(err) => {
+                        writeln!(out, "{}", err)?;
+                        continue;
+                    }
+                },
+            }
+            self.show(out)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(sample: &str, commands: &str) -> (Session, String) {
+        let mut session = Session::new(sample);
+        let mut out = Vec::new();
+        session.run(&mut commands.as_bytes(), &mut out).unwrap();
+        (session, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn redraws_after_each_command() {
+        let (session, out) = session("Back,", "mark forward_word\ncopy\n");
+
+        assert!(out.contains("> ^Back|,\n"));
+        assert!(out.contains("clipboard[0] = \"Back\""));
+        assert_eq!("mark forward_word\ncopy", session.program());
+    }
+
+    #[test]
+    fn undo_and_invalid_commands() {
+        let (session, out) = session("Back,", "forward\nfrobnicate\nupcase_line\n:undo\n");
+
+        assert!(out.contains("Unknown command `frobnicate` on line 1."));
+        assert_eq!("forward", session.program());
+        assert_eq!("B|ack,", session.state.render());
+    }
+}
@@ -0,0 +1,246 @@
+//! Translation of simple sed scripts into programs.
+//!
+//! Only the `s` command with literal patterns and the `y` command are
+//! supported, separated by `;` or newlines. Addresses, regular expressions,
+//! back references and every other command are rejected.
+use cmd::Cmd;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A sed script that can not be translated.
+#[derive(Debug, PartialEq)]
+pub enum SedError {
+    /// A sed feature without a lined equivalent.
+    Unsupported(String),
+    /// A script that is not valid sed.
+    Invalid(String),
+}
+
+impl fmt::Display for SedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SedError::Unsupported(feature) => write!(f, "Unsupported sed feature: {}.", feature),
+            SedError::Invalid(reason) => write!(f, "Invalid sed script: {}.", reason),
+        }
+    }
+}
+
+impl Error for SedError {}
+
+fn unsupported<T>(feature: &str) -> Result<T, SedError> {
+    Err(SedError::Unsupported(feature.to_owned()))
+}
+
+/// Characters with a special meaning in a basic regular expression.
+const REGEX_CHARS: &str = ".*[]^$";
+
+/// The part of an `s` or `y` command up to the next unescaped `delimiter`.
+/// `escaped` translates the character after a backslash, unescaped characters
+/// in `special` are rejected.
+fn delimited<F>(
+    chars: &mut Peekable<Chars>,
+    delimiter: char,
+    special: &str,
+    mut escaped: F,
+) -> Result<String, SedError>
+where
+    F: FnMut(char) -> Result<char, SedError>,
+{
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) if c == delimiter => text.push(c),
+                Some(c) => text.push(escaped(c)?),
+                None => break,
+            },
+            Some(c) if c == delimiter => return Ok(text),
+            Some('\n') | None => break,
+            Some(c) if special.contains(c) => {
+                return unsupported(&format!("regular expression character `{}`", c))
+            }
+            Some(c) => text.push(c),
+        }
+    }
+    Err(SedError::Invalid(format!(
+        "unterminated `{}` delimited text",
+        delimiter
+    )))
+}
+
+fn pattern(chars: &mut Peekable<Chars>, delimiter: char) -> Result<String, SedError> {
+    let pattern = delimited(chars, delimiter, REGEX_CHARS, |c| match c {
+        '\\' => Ok(c),
+        c if REGEX_CHARS.contains(c) => Ok(c),
+        'n' => unsupported("newlines in patterns"),
+        c => unsupported(&format!("regular expression `\\{}`", c)),
+    })?;
+    if pattern.is_empty() {
+        return unsupported("empty patterns");
+    }
+    Ok(pattern)
+}
+
+fn substitute(chars: &mut Peekable<Chars>) -> Result<Cmd, SedError> {
+    let delimiter = match chars.next() {
+        Some(c) if c != '\\' && c != '\n' => c,
+        _ => return Err(SedError::Invalid("missing delimiter after `s`".to_owned())),
+    };
+
+    let from = pattern(chars, delimiter)?;
+
+    let mut to = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) if c == delimiter || c == '\\' || c == '&' => to.push(c),
+                Some(c) if c.is_ascii_digit() => return unsupported("back references"),
+                Some('\n') => to.push('\n'),
+                Some(c) => return unsupported(&format!("escape `\\{}` in replacements", c)),
+                None => break,
+            },
+            Some('&') => return unsupported("`&` in replacements"),
+            Some(c) if c == delimiter => {
+                let all = flags(chars)?;
+                return Ok(Cmd::Substitute { from, to, all });
+            }
+            Some('\n') | None => break,
+            Some(c) => to.push(c),
+        }
+    }
+    Err(SedError::Invalid("unterminated `s` command".to_owned()))
+}
+
+/// Parses the flags of an `s` command, returning true if all occurrences are replaced.
+fn flags(chars: &mut Peekable<Chars>) -> Result<bool, SedError> {
+    let mut all = false;
+    while let Some(&c) = chars.peek() {
+        match c {
+            'g' => all = true,
+            ';' | '\n' | '}' | '#' => break,
+            c if c.is_whitespace() => (),
+            c => return unsupported(&format!("`s` flag `{}`", c)),
+        }
+        chars.next();
+    }
+    Ok(all)
+}
+
+fn translate(chars: &mut Peekable<Chars>) -> Result<Cmd, SedError> {
+    let delimiter = match chars.next() {
+        Some(c) if c != '\\' && c != '\n' => c,
+        _ => return Err(SedError::Invalid("missing delimiter after `y`".to_owned())),
+    };
+    let escaped = |c| match c {
+        '\\' => Ok(c),
+        'n' => unsupported("newlines in `y`"),
+        c => Err(SedError::Invalid(format!(
+            "unknown escape `\\{}` in `y`",
+            c
+        ))),
+    };
+    let from = delimited(chars, delimiter, "", escaped)?;
+    let to = delimited(chars, delimiter, "", escaped)?;
+    if from.chars().count() != to.chars().count() {
+        return Err(SedError::Invalid(
+            "`y` strings have different lengths".to_owned(),
+        ));
+    }
+    Ok(Cmd::Translate {
+        table: from.chars().zip(to.chars()).collect(),
+    })
+}
+
+/// Translates a sed script into commands.
+pub fn translate_script(script: &str) -> Result<Vec<Cmd>, SedError> {
+    let mut chars = script.chars().peekable();
+    let mut cmds = Vec::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => (),
+            c if c.is_whitespace() => (),
+            '#' => while chars.next().is_some_and(|c| c != '\n') {},
+            's' => cmds.push(substitute(&mut chars)?),
+            'y' => cmds.push(translate(&mut chars)?),
+            'd' => cmds.push(Cmd::DeleteLine),
+            'p' => cmds.push(Cmd::Print),
+            'h' => cmds.push(Cmd::Hold),
+            'H' => cmds.push(Cmd::HoldAppend),
+            'g' => cmds.push(Cmd::Get),
+            'G' => cmds.push(Cmd::GetAppend),
+            'x' => cmds.push(Cmd::Exchange),
+            c if c.is_ascii_digit() || c == '/' || c == '$' || c == '\\' => {
+                return unsupported("addresses")
+            }
+            '{' | '}' | '!' => return unsupported("blocks"),
+            c => return unsupported(&format!("command `{}`", c)),
+        }
+    }
+
+    if cmds.is_empty() {
+        return Err(SedError::Invalid("no commands".to_owned()));
+    }
+    // `d` ends the cycle and, without addresses, always runs.
+    if let Some(delete) = cmds.iter().position(|cmd| *cmd == Cmd::DeleteLine) {
+        cmds.truncate(delete + 1);
+    }
+    Ok(cmds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cmd::run;
+    use parser;
+
+    #[test]
+    fn translate_substitutions() {
+        let program = translate_script("s/foo/bar/g; y/abc/xyz/\ns|a\\.b|a\\|b|").unwrap();
+        assert_eq!(
+            "substitute_all \"foo\" \"bar\"\ntranslate \"abc\" \"xyz\"\nsubstitute \"a.b\" \"a|b\"\n",
+            parser::unparse(&program)
+        );
+    }
+
+    #[test]
+    fn translate_output_and_hold_commands() {
+        assert_eq!(
+            vec![Cmd::Print, Cmd::DeleteLine],
+            translate_script("p;d").unwrap()
+        );
+        assert_eq!(
+            vec![Cmd::GetAppend, Cmd::Hold, Cmd::Exchange],
+            translate_script("G;h;x").unwrap()
+        );
+    }
+
+    #[test]
+    fn delete_ends_the_script() {
+        let program = translate_script("d;p").unwrap();
+        assert_eq!(vec![Cmd::DeleteLine], program);
+        assert!(run(&program, "a").is_empty());
+        assert!(translate_script("d;q").is_err());
+    }
+
+    #[test]
+    fn reject_unsupported_features() {
+        let feature = |script| match translate_script(script) {
+            Err(SedError::Unsupported(feature)) => feature,
+            result => panic!("{:?}", result),
+        };
+        assert_eq!("regular expression character `*`", feature("s/a*/b/"));
+        assert_eq!("back references", feature(r"s/a/\1/"));
+        assert_eq!("`s` flag `p`", feature("s/a/b/p"));
+        assert_eq!("addresses", feature("1d"));
+        assert_eq!("command `q`", feature("q"));
+        assert_eq!(
+            Err(SedError::Invalid(
+                "`y` strings have different lengths".to_owned()
+            )),
+            translate_script("y/ab/x/")
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Runs the inline test cases of a program file.
+use cmd;
+use cmd::Cmd;
+use parser::Program;
+use std::io;
+use std::io::Write;
+
+/// Writes the expected and actual output, with a `^` under the first
+/// character that differs.
+fn write_diff<W: Write>(expected: &str, actual: &str, out: &mut W) -> io::Result<()> {
+    let same = expected
+        .chars()
+        .zip(actual.chars())
+        .take_while(|(expected, actual)| expected == actual)
+        .count();
+    writeln!(out, "  - {}", expected)?;
+    writeln!(out, "  + {}", actual)?;
+    writeln!(out, "    {}^", " ".repeat(same))
+}
+
+/// Runs every test case of `program` and reports the results to `out`.
+/// Test cases edit single lines, so `at_begin` and `at_end` blocks are
+/// skipped. Returns the number of failed tests.
+pub fn run_tests<W: Write>(program: &Program, out: &mut W) -> io::Result<usize> {
+    if !program.begin.is_empty() || !program.end.is_empty() {
+        writeln!(out, "at_begin and at_end blocks are skipped")?;
+    }
+    let cmds: Vec<Cmd> = program.cmds.iter().map(|(_, cmd)| cmd.clone()).collect();
+    let tests = &program.tests;
+    let mut failed = 0;
+    for test in tests {
+        let actual = cmd::run(&cmds, &test.input).join("\n");
+        if actual == test.expected {
+            writeln!(out, "test on line {} ... ok", test.line)?;
+        } else {
+            failed += 1;
+            writeln!(out, "test on line {} ... FAILED", test.line)?;
+            writeln!(out, "  input {:?}", test.input)?;
+            write_diff(&test.expected, &actual, out)?;
+        }
+    }
+    writeln!(out, "{} passed, {} failed", tests.len() - failed, failed)?;
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser;
+    use tokenizer::TokenizerTrait;
+
+    #[test]
+    fn report_failures_with_diff() {
+        let text = r#"trim_line upcase_line
+test "  ab " => "AB"
+test "abc" => "ABD""#;
+        let program = parser::parse_program(&mut text.chars().tokens()).unwrap();
+        let mut out = Vec::new();
+
+        assert_eq!(1, run_tests(&program, &mut out).unwrap());
+        assert_eq!(
+            "test on line 2 ... ok
+test on line 3 ... FAILED
+  input \"abc\"
+  - ABD
+  + ABC
+      ^
+1 passed, 1 failed
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn report_skipped_blocks() {
+        let text = r#"at_begin { insert "x" } upcase_line test "a" => "A" at_end { print }"#;
+        let program = parser::parse_program(&mut text.chars().tokens()).unwrap();
+        let mut out = Vec::new();
+
+        assert_eq!(0, run_tests(&program, &mut out).unwrap());
+        assert_eq!(
+            "at_begin and at_end blocks are skipped\ntest on line 1 ... ok\n1 passed, 0 failed\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 pub enum Token {
     LPAREN(TokenInfo),
@@ -11,7 +12,9 @@ pub enum Token {
 
 pub struct Tokenizer<'a> {
     buf: Vec<char>,
-    chars: &'a mut Iterator<Item = char>,
+    /// Tokens to return before reading on, last first.
+    pending: Vec<Token>,
+    chars: &'a mut dyn Iterator<Item = char>,
     col: usize,
     line: usize,
 }
@@ -22,6 +25,26 @@ pub struct TokenInfo {
     column: usize,
 }
 
+impl TokenInfo {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl Token {
+    pub fn info(&self) -> &TokenInfo {
+        match self {
+            Token::LPAREN(info)
+            | Token::RPAREN(info)
+            | Token::LBRACE(info)
+            | Token::RBRACE(info)
+            | Token::WORD(info, _)
+            | Token::NUM(info, _)
+            | Token::STRING(info, _) => info,
+        }
+    }
+}
+
 impl<'a> Tokenizer<'a> {
     fn info(&self) -> TokenInfo {
         TokenInfo {
@@ -30,13 +53,17 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
-    fn word_or_token(&mut self, token: Token, c: char) -> Token {
+    /// Returns `token` to the tokenizer, to be read again next.
+    pub fn push_back(&mut self, token: Token) {
+        self.pending.push(token);
+    }
+
+    fn word_or_token(&mut self, token: Token) -> Token {
         if self.buf.is_empty() {
             token
         } else {
-            let result = self.word();
-            self.buf.push(c);
-            result
+            self.pending.push(token);
+            self.word()
         }
     }
 
@@ -85,14 +112,17 @@ impl<'a> Iterator for Tokenizer<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Token> {
+        if let Some(token) = self.pending.pop() {
+            return Some(token);
+        }
         while let Some(c) = self.chars.next() {
             self.col += 1;
 
             match c {
-                '(' => return { let info = self.info(); Some(self.word_or_token(Token::LPAREN(info), '('))},
-                ')' => return  { let info = self.info(); Some(self.word_or_token(Token::LPAREN(info), ')'))},
-                '{' => return  { let info = self.info(); Some(self.word_or_token(Token::LBRACE(info), '{'))},
-                '}' => return  { let info = self.info(); Some(self.word_or_token(Token::RBRACE(info), '}'))},
+                '(' => return { let info = self.info(); Some(self.word_or_token(Token::LPAREN(info)))},
+                ')' => return  { let info = self.info(); Some(self.word_or_token(Token::RPAREN(info)))},
+                '{' => return  { let info = self.info(); Some(self.word_or_token(Token::LBRACE(info)))},
+                '}' => return  { let info = self.info(); Some(self.word_or_token(Token::RBRACE(info)))},
                 '"' => // assert empty buffer 
                     return Some(self.quoted_string()),
                 '\n'  => {if !self.buf.is_empty() { let token = Some(self.word());self.nextline();return token;} else {self.nextline();}},
@@ -112,13 +142,14 @@ pub trait TokenizerTrait<'a, I>: Sized
 where
     I: Iterator<Item = char>,
 {
-    fn tokens(self: &'a mut Self) -> Tokenizer<'a>;
+    fn tokens(&'a mut self) -> Tokenizer<'a>;
 }
 
 impl<'a, I: Iterator<Item = char>> TokenizerTrait<'a, I> for I {
-    fn tokens(self: &'a mut Self) -> Tokenizer<'a> {
+    fn tokens(&'a mut self) -> Tokenizer<'a> {
         Tokenizer {
             buf: Vec::new(),
+            pending: Vec::new(),
             chars: self,
             line: 1,
             col: 0,
@@ -144,6 +175,16 @@ mod tests {
         Some(STRING(TokenInfo { line, column }, s.to_owned()))
     }
 
+    #[test]
+    fn tokenizer_parens() {
+        let mut chars = "()".chars();
+        let mut tokens = chars.tokens();
+
+        assert_eq!(Some(LPAREN(TokenInfo { line: 1, column: 1 })), tokens.next());
+        assert_eq!(Some(RPAREN(TokenInfo { line: 1, column: 2 })), tokens.next());
+        assert_eq!(None, tokens.next());
+    }
+
     #[test]
     fn tokenizer_stream() {
         let input = r#"trim_line
@@ -166,4 +207,15 @@ insert "const KW_"
 
         assert_eq!(None, tokens.next());
     }
+
+    #[test]
+    fn tokenizer_braces_end_words() {
+        let mut chars = "{home}".chars();
+        let mut tokens = chars.tokens();
+
+        assert_eq!(Some(LBRACE(TokenInfo { line: 1, column: 1 })), tokens.next());
+        assert_eq!(word("home", 1, 6), tokens.next());
+        assert_eq!(Some(RBRACE(TokenInfo { line: 1, column: 6 })), tokens.next());
+        assert_eq!(None, tokens.next());
+    }
 }
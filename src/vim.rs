@@ -0,0 +1,352 @@
+//! A program syntax made of vim normal mode keys.
+//!
+//! Supported are the motions `0 $ h l w b f{c} F{c}`, the operators `d`, `y`,
+//! `gU` and `gu` combined with a motion or the `iw` text object, `x X D p P`,
+//! `gUU guu` and inserting with `i a I A` up to `<Esc>`. Counts work as in
+//! vim. Whitespace between commands is ignored and `"` starts a comment that
+//! lasts to the end of the line.
+//!
+//! As in a vim macro, a failed `f` or `F` motion stops editing the line, so
+//! an operator with that motion does nothing.
+use cmd::Cmd;
+use emacs::MAX_COUNT;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, PartialEq)]
+pub enum VimError {
+    /// Keys at a position in the program that have no corresponding command.
+    UnsupportedKeys { position: usize, keys: String },
+    /// A count larger than `MAX_COUNT`, at a position in the program.
+    InvalidCount { position: usize, keys: String },
+}
+
+impl fmt::Display for VimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VimError::UnsupportedKeys { position, keys } => {
+                write!(f, "Unsupported vim keys `{}` at {}.", keys, position)
+            }
+            VimError::InvalidCount { position, keys } => write!(
+                f,
+                "Invalid count `{}` at {}, counts go up to {}.",
+                keys, position, MAX_COUNT
+            ),
+        }
+    }
+}
+
+impl Error for VimError {}
+
+enum Operator {
+    Delete,
+    Yank,
+    Upcase,
+    Downcase,
+}
+
+/// Where a motion moves the cursor, relative to where it started.
+enum Direction {
+    Forward,
+    Backward,
+}
+
+struct Keys<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    text: &'a str,
+    /// Set when a count is larger than `MAX_COUNT`.
+    too_large: bool,
+}
+
+impl<'a> Keys<'a> {
+    fn error(&self, start: usize) -> VimError {
+        let end = self
+            .chars
+            .clone()
+            .next()
+            .map(|(pos, _)| pos)
+            .unwrap_or_else(|| self.text.len());
+        let position = start;
+        let keys = self.text[start..end].to_owned();
+        if self.too_large {
+            VimError::InvalidCount { position, keys }
+        } else {
+            VimError::UnsupportedKeys { position, keys }
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+
+    fn skip_blanks_and_comments(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            match c {
+                '"' => while self.next().is_some_and(|c| c != '\n') {},
+                c if c.is_whitespace() => {
+                    self.next();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Reads a count, which is `None` if it is larger than `MAX_COUNT`.
+    fn count(&mut self) -> Option<usize> {
+        let mut count: usize = 0;
+        while let Some(&(_, c)) = self.chars.peek() {
+            match c.to_digit(10) {
+                Some(digit) if count > 0 || digit > 0 => {
+                    self.next();
+                    count = self.limit(count * 10 + digit as usize)?;
+                }
+                _ => break,
+            }
+        }
+        Some(count)
+    }
+
+    /// Checks that a count, or a product of counts, is at most `MAX_COUNT`.
+    fn limit(&mut self, count: usize) -> Option<usize> {
+        self.too_large = count > MAX_COUNT;
+        if self.too_large {
+            None
+        } else {
+            Some(count)
+        }
+    }
+
+    /// Text typed in insert mode, up to `<Esc>` or the end of the program.
+    fn inserted_text(&mut self) -> String {
+        let rest = &self.text[self.chars.peek().map_or(self.text.len(), |&(pos, _)| pos)..];
+        let text = &rest[..rest.find("<Esc>").unwrap_or(rest.len())];
+        let skip = text.chars().count() + if text.len() < rest.len() { 5 } else { 0 };
+        for _ in 0..skip {
+            self.next();
+        }
+        text.to_owned()
+    }
+}
+
+fn repeated(times: usize, cmds: Vec<Cmd>) -> Cmd {
+    let cmd = if cmds.len() == 1 {
+        cmds.into_iter().next().unwrap()
+    } else {
+        Cmd::Block(cmds)
+    };
+    if times > 1 {
+        Cmd::Repeat {
+            times,
+            cmd: Box::new(cmd),
+        }
+    } else {
+        cmd
+    }
+}
+
+/// Parses a motion, returning the commands moving the cursor to the end of
+/// the affected text, exclusive.
+fn motion(key: char, keys: &mut Keys) -> Option<(Cmd, Direction)> {
+    let motion = match key {
+        '0' => (Cmd::Home, Direction::Backward),
+        '$' => (Cmd::End, Direction::Forward),
+        'h' => (Cmd::Back, Direction::Backward),
+        'l' => (Cmd::Forward, Direction::Forward),
+        'w' => (Cmd::ForwardWordStart, Direction::Forward),
+        'b' => (Cmd::BackWordStart, Direction::Backward),
+        'f' => {
+            let what = keys.next()?;
+            (
+                Cmd::Block(vec![Cmd::FindNext { what }, Cmd::Forward]),
+                Direction::Forward,
+            )
+        }
+        'F' => (Cmd::RFind { what: keys.next()? }, Direction::Backward),
+        _ => return None,
+    };
+    Some(motion)
+}
+
+/// Applies `operator` to the text between the cursor and where `cmd` moves it,
+/// leaving the cursor at the start of that text.
+fn operate(operator: &Operator, cmd: Cmd, direction: &Direction) -> Vec<Cmd> {
+    let mut cmds = vec![Cmd::Mark, cmd];
+    match operator {
+        Operator::Delete => cmds.push(Cmd::Cut),
+        Operator::Yank => cmds.push(Cmd::Copy),
+        Operator::Upcase => cmds.push(Cmd::UpcaseRegion),
+        Operator::Downcase => cmds.push(Cmd::DowncaseRegion),
+    }
+    if let (Direction::Forward, false) = (direction, matches!(operator, Operator::Delete)) {
+        cmds.push(Cmd::ExchangeMark);
+    }
+    cmds
+}
+
+fn operator_cmds(operator: Operator, keys: &mut Keys) -> Option<(usize, Vec<Cmd>)> {
+    let count = keys.count()?;
+    let key = keys.next()?;
+
+    let cmds = match (key, &operator) {
+        ('U', Operator::Upcase) => vec![Cmd::UpcaseLine],
+        ('u', Operator::Downcase) => vec![Cmd::DowncaseLine],
+        ('i', _) if keys.next()? == 'w' => {
+            let region = match operator {
+                Operator::Delete => Cmd::Cut,
+                Operator::Yank => Cmd::Copy,
+                Operator::Upcase => Cmd::UpcaseRegion,
+                Operator::Downcase => Cmd::DowncaseRegion,
+            };
+            vec![Cmd::SelectWord, region]
+        }
+        _ => {
+            let (cmd, direction) = motion(key, keys)?;
+            let cmd = repeated(count, vec![cmd]);
+            return Some((1, operate(&operator, cmd, &direction)));
+        }
+    };
+    Some((count, cmds))
+}
+
+fn command(key: char, keys: &mut Keys) -> Option<(usize, Vec<Cmd>)> {
+    let cmds = match key {
+        '$' => vec![Cmd::Last],
+        'f' => vec![Cmd::FindNext { what: keys.next()? }],
+        'x' => operate(&Operator::Delete, Cmd::Forward, &Direction::Forward),
+        'X' => operate(&Operator::Delete, Cmd::Back, &Direction::Backward),
+        'D' => operate(&Operator::Delete, Cmd::End, &Direction::Forward),
+        'p' => vec![Cmd::PasteAfter],
+        'P' => vec![Cmd::Paste, Cmd::Back],
+        'd' => return operator_cmds(Operator::Delete, keys),
+        'y' => return operator_cmds(Operator::Yank, keys),
+        'g' => match keys.next()? {
+            'U' => return operator_cmds(Operator::Upcase, keys),
+            'u' => return operator_cmds(Operator::Downcase, keys),
+            _ => return None,
+        },
+        'i' | 'a' | 'I' | 'A' => {
+            let mut cmds = match key {
+                'a' => vec![Cmd::Forward],
+                'I' => vec![Cmd::Home],
+                'A' => vec![Cmd::End],
+                _ => vec![],
+            };
+            cmds.push(Cmd::Insert {
+                what: keys.inserted_text(),
+            });
+            cmds.push(Cmd::Back);
+            cmds
+        }
+        _ => vec![motion(key, keys)?.0],
+    };
+    Some((1, cmds))
+}
+
+/// Parses a program written as vim normal mode keys.
+pub fn parse(text: &str) -> Result<Vec<Cmd>, VimError> {
+    let mut keys = Keys {
+        chars: text.char_indices().peekable(),
+        text,
+        too_large: false,
+    };
+    let mut cmds = Vec::new();
+
+    loop {
+        keys.skip_blanks_and_comments();
+        let start = match keys.chars.peek() {
+            Some(&(pos, _)) => pos,
+            None => return Ok(cmds),
+        };
+
+        let parsed = keys.count().and_then(|count| {
+            let (times, parsed) = command(keys.next()?, &mut keys)?;
+            let times = keys.limit(count.max(1).saturating_mul(times))?;
+            Some(repeated(times, parsed))
+        });
+        match parsed {
+            Some(cmd) => cmds.push(cmd),
+            None => return Err(keys.error(start)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cmd::LineState;
+
+    fn edit(program: &str, line: &str) -> String {
+        let mut state = LineState::new(line);
+        for cmd in parse(program).unwrap() {
+            if !state.done_with_line {
+                cmd.eval(&mut state);
+            }
+        }
+        state.render().replace('^', "")
+    }
+
+    #[test]
+    fn motions() {
+        assert_eq!("let |x = 1;", edit("w", "let x = 1;"));
+        assert_eq!("let x = |1;", edit("3w", "let x = 1;"));
+        assert_eq!("let x |= 1;", edit("$ 2b", "let x = 1;"));
+        assert_eq!("let x = 1|;", edit("$", "let x = 1;"));
+        assert_eq!("|let x = 1;", edit("w0", "let x = 1;"));
+        assert_eq!("a, b|, c", edit("2f,", "a, b, c"));
+        assert_eq!("|", edit("$", ""));
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!("|x = 1;", edit("dw", "let x = 1;"));
+        assert_eq!("let |", edit("wd$", "let x = 1;"));
+        assert_eq!("let |1;", edit("w d2w", "let x = 1;"));
+        assert_eq!("|b, c", edit("df,x", "a, b, c"));
+        assert_eq!("|abc", edit("df;", "abc"));
+        assert_eq!("|abc", edit("dF;x", "abc"));
+        assert_eq!("let |FOO = 1;", edit("wgUiw", "let foo = 1;"));
+        assert_eq!("LET |FOO", edit("wgUU", "let foo"));
+        assert_eq!("let x |", edit("2wD", "let x = 1;"));
+    }
+
+    #[test]
+    fn yank_and_put() {
+        assert_eq!("let |x = 1;", edit("wyw", "let x = 1;"));
+        assert_eq!("x let = 1;", edit("dwwP", "let x = 1;").replace('|', ""));
+        assert_eq!("bac", edit("xp", "abc").replace('|', ""));
+    }
+
+    #[test]
+    fn insert_and_comments() {
+        let program = "\" prefix every line\nIconst <Esc>A;<Esc>";
+        assert_eq!("const x|;", edit(program, "x"));
+    }
+
+    #[test]
+    fn unsupported_keys() {
+        assert_eq!(
+            Err(VimError::UnsupportedKeys {
+                position: 2,
+                keys: "2Q".to_owned(),
+            }),
+            parse("w 2Qb")
+        );
+        assert_eq!(
+            Err(VimError::InvalidCount {
+                position: 0,
+                keys: "100001".to_owned(),
+            }),
+            parse("1000010000000x")
+        );
+        assert_eq!(
+            Err(VimError::InvalidCount {
+                position: 1,
+                keys: "2d100001".to_owned(),
+            }),
+            parse(" 2d100001w")
+        );
+        assert!(parse("100000x").is_ok());
+    }
+}
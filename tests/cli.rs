@@ -0,0 +1,23 @@
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+#[test]
+fn in_place_with_attached_backup_suffix() {
+    let dir = env::temp_dir().join(format!("lined-cli-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("words.txt");
+    fs::write(&path, "back\nforward\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_lined"))
+        .args(["-p", "upcase_line", "-i.bak"])
+        .arg(&path)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!("BACK\nFORWARD\n", fs::read_to_string(&path).unwrap());
+    let backup = fs::read_to_string(dir.join("words.txt.bak")).unwrap();
+    assert_eq!("back\nforward\n", backup);
+    fs::remove_dir_all(&dir).unwrap();
+}